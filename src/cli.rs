@@ -0,0 +1,200 @@
+use clap::{Args, Parser, Subcommand};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Index blockchain blocks and transactions into the configured sinks
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// TOML or YAML file with settings; environment variables and flags take precedence
+    /// [CONFIG_FILE]
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum Command {
+    /// Backfill up to the chain head, then follow new blocks (the default)
+    Run,
+    /// Index up to `--to` (default: the chain head) and exit without entering live sync
+    Backfill {
+        /// First block to index; the range is then indexed without moving the checkpoint
+        #[arg(long)]
+        from: Option<u64>,
+        /// Last block to index
+        #[arg(long)]
+        to: Option<u64>,
+    },
+    /// Fetch and overwrite the stored blocks in a range, regardless of the checkpoint
+    Reindex {
+        #[arg(long)]
+        from: u64,
+        #[arg(long)]
+        to: u64,
+    },
+    /// Print the checkpoint, the chain head and the lag between them
+    Status,
+    /// Compare stored blocks with the chain
+    Verify {
+        /// First block to check (default: START_BLOCK)
+        #[arg(long)]
+        from: Option<u64>,
+        /// Last block to check (default: the checkpoint)
+        #[arg(long)]
+        to: Option<u64>,
+        /// Check this many randomly chosen blocks instead of the whole range
+        #[arg(long)]
+        sample: Option<u64>,
+        /// Re-index the blocks that do not match
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Backfill blocks missing below the checkpoint
+    Repair {
+        /// Only report the missing blocks
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Move the checkpoint to block `--to`
+    ResetCheckpoint {
+        #[arg(long)]
+        to: u64,
+    },
+    /// Create the indices or tables of every sink and exit
+    InitIndices,
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Subcommand)]
+pub enum ConfigCommand {
+    /// Validate the configuration and print the effective settings, with secrets redacted
+    Check,
+}
+
+/// Flags taking precedence over the environment variable of the same setting
+#[derive(Debug, Default, Args)]
+pub struct ConfigOverrides {
+    /// RPC node URL [RPC_HTTP_URL]
+    #[arg(long, global = true, value_name = "URL")]
+    pub rpc_url: Option<String>,
+    /// WebSocket RPC URL for live sync [RPC_WS_URL]
+    #[arg(long, global = true, value_name = "URL")]
+    pub ws_url: Option<String>,
+    /// Elasticsearch URL [ES_URL]
+    #[arg(long, global = true, value_name = "URL")]
+    pub es_url: Option<String>,
+    /// Prefix of the index names [INDEX_PREFIX]
+    #[arg(long, global = true)]
+    pub index_prefix: Option<String>,
+    /// Blocks per batch [BATCH_SIZE]
+    #[arg(long, global = true)]
+    pub batch_size: Option<usize>,
+    /// Initial number of blocks fetched concurrently [CONCURRENCY]
+    #[arg(long, global = true)]
+    pub concurrency: Option<usize>,
+    /// Blocks per Elasticsearch bulk request [ES_BULK_SIZE]
+    #[arg(long, global = true)]
+    pub es_bulk_size: Option<usize>,
+    /// First block to index [START_BLOCK]
+    #[arg(long, global = true)]
+    pub start_block: Option<u64>,
+    /// Address of the metrics and health check server [HTTP_ADDR]
+    #[arg(long, global = true, value_name = "ADDR")]
+    pub http_addr: Option<SocketAddr>,
+}
+
+impl ConfigOverrides {
+    /// The flags that were given, as environment variable names and values
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let vars = [
+            ("RPC_HTTP_URL", self.rpc_url.clone()),
+            ("RPC_WS_URL", self.ws_url.clone()),
+            ("ES_URL", self.es_url.clone()),
+            ("INDEX_PREFIX", self.index_prefix.clone()),
+            ("BATCH_SIZE", self.batch_size.map(|v| v.to_string())),
+            ("CONCURRENCY", self.concurrency.map(|v| v.to_string())),
+            ("ES_BULK_SIZE", self.es_bulk_size.map(|v| v.to_string())),
+            ("START_BLOCK", self.start_block.map(|v| v.to_string())),
+            ("HTTP_ADDR", self.http_addr.map(|v| v.to_string())),
+        ];
+
+        vars.into_iter()
+            .filter_map(|(name, value)| Some((name, value?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn test_cli_definition() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_defaults_to_run() {
+        let cli = Cli::try_parse_from(["indexer"]).unwrap();
+        assert_eq!(cli.command, None);
+        assert!(cli.overrides.vars().is_empty());
+    }
+
+    #[test]
+    fn test_subcommands_and_overrides() {
+        let cli = Cli::try_parse_from([
+            "indexer",
+            "backfill",
+            "--from",
+            "100",
+            "--to",
+            "200",
+            "--batch-size",
+            "50",
+            "--es-url",
+            "http://es:9200",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            cli.command,
+            Some(Command::Backfill {
+                from: Some(100),
+                to: Some(200)
+            })
+        );
+        assert_eq!(
+            cli.overrides.vars(),
+            vec![
+                ("ES_URL", "http://es:9200".to_string()),
+                ("BATCH_SIZE", "50".to_string()),
+            ]
+        );
+
+        let cli = Cli::try_parse_from(["indexer", "reset-checkpoint", "--to", "42"]).unwrap();
+        assert_eq!(cli.command, Some(Command::ResetCheckpoint { to: 42 }));
+
+        assert!(Cli::try_parse_from(["indexer", "reindex", "--from", "1"]).is_err());
+
+        let cli = Cli::try_parse_from(["indexer", "config", "check", "--config", "indexer.toml"])
+            .unwrap();
+        assert_eq!(
+            cli.command,
+            Some(Command::Config {
+                command: ConfigCommand::Check
+            })
+        );
+        assert_eq!(cli.config, Some(PathBuf::from("indexer.toml")));
+    }
+}
@@ -1,22 +1,191 @@
-use crate::config::Config;
+use crate::config::{Config, TransactionStorage};
 use crate::error::IndexerError;
-use crate::models::IndexedBlock;
+use crate::metrics::METRICS;
+use crate::models::{FinalityStatus, IndexedBlock, IndexedTransaction};
+use crate::progress::SyncProgress;
+use crate::sink::Sink;
 use anyhow::Result;
+use async_trait::async_trait;
 use elasticsearch::{
     http::transport::Transport,
     indices::{IndicesCreateParts, IndicesExistsParts, IndicesRefreshParts},
-    BulkOperation, BulkParts, Elasticsearch, GetParts, IndexParts,
+    params::Conflicts,
+    BulkOperation, BulkParts, DeleteByQueryParts, Elasticsearch, GetParts, IndexParts, SearchParts,
+    UpdateByQueryParts,
 };
 use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::time::{sleep, Duration};
+
+/// Flat transaction documents fetched per search request
+const FLAT_TRANSACTIONS_PAGE_SIZE: usize = 10_000;
 
 pub struct ElasticsearchClient {
     client: Elasticsearch,
     blocks_index: String,
+    transactions_index: String,
+    logs_index: String,
     meta_index: String,
+    transaction_storage: TransactionStorage,
+    index_logs: bool,
+    max_retries: u32,
+}
+
+/// A single document of a bulk request, kept so that rejected items can be resent
+struct BulkItem {
+    block_number: u64,
+    index: String,
+    id: String,
+    doc: Value,
+}
+
+/// An item rejected in a bulk response, by its position in the request
+#[derive(Debug, PartialEq)]
+struct BulkItemFailure {
+    position: usize,
+    status: u16,
+    reason: String,
+}
+
+/// Statuses worth retrying: overload, unavailable shards and transport errors (0)
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 0 | 429 | 502 | 503 | 504)
+}
+
+/// Extract the failed items of a bulk response body
+fn bulk_failures(body: &Value) -> Vec<BulkItemFailure> {
+    if !body["errors"].as_bool().unwrap_or(false) {
+        return Vec::new();
+    }
+
+    let Some(items) = body["items"].as_array() else {
+        return Vec::new();
+    };
+
+    items
+        .iter()
+        .enumerate()
+        .filter_map(|(position, item)| {
+            // Each item is keyed by its action: {"index": {...}}
+            let result = item.as_object()?.values().next()?;
+            let status = result["status"].as_u64().unwrap_or(0) as u16;
+            if (200..300).contains(&status) {
+                return None;
+            }
+
+            let reason = result["error"]["reason"]
+                .as_str()
+                .or_else(|| result["error"]["type"].as_str())
+                .unwrap_or("unknown error")
+                .to_string();
+
+            Some(BulkItemFailure {
+                position,
+                status,
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// Mark every item of a request as failed, for errors affecting the whole request
+fn all_failed(count: usize, status: u16, reason: String) -> Vec<BulkItemFailure> {
+    (0..count)
+        .map(|position| BulkItemFailure {
+            position,
+            status,
+            reason: reason.clone(),
+        })
+        .collect()
+}
+
+/// Deleted documents and version conflicts of a delete-by-query response, or an error when it
+/// reports search or shard failures
+fn delete_by_query_counts(body: &Value) -> Result<(u64, u64)> {
+    if let Some(failures) = body["failures"].as_array().filter(|f| !f.is_empty()) {
+        return Err(IndexerError::Elasticsearch(format!(
+            "Delete by query failed with {} failure(s), first: {}",
+            failures.len(),
+            failures[0]
+        ))
+        .into());
+    }
+
+    let deleted = body["deleted"].as_u64().unwrap_or(0);
+    let conflicts = body["version_conflicts"].as_u64().unwrap_or(0);
+    Ok((deleted, conflicts))
+}
+
+/// Updated documents of an update by query response, or an error if any of them failed
+fn update_by_query_count(body: &Value) -> Result<u64> {
+    if let Some(failures) = body["failures"].as_array().filter(|f| !f.is_empty()) {
+        return Err(IndexerError::Elasticsearch(format!(
+            "Update by query failed with {} failure(s), first: {}",
+            failures.len(),
+            failures[0]
+        ))
+        .into());
+    }
+
+    Ok(body["updated"].as_u64().unwrap_or(0))
+}
+
+/// Group flat transaction search hits by block, each block's transactions in block order
+fn transactions_by_block(hits: &[Value]) -> Result<HashMap<u64, Vec<IndexedTransaction>>> {
+    let mut transactions: HashMap<u64, Vec<IndexedTransaction>> = HashMap::new();
+    for hit in hits {
+        let block_number = hit["_source"]["block_number"].as_u64().ok_or_else(|| {
+            IndexerError::Serialization(format!("Transaction without block_number: {}", hit))
+        })?;
+        let transaction: IndexedTransaction = serde_json::from_value(hit["_source"].clone())
+            .map_err(|e| IndexerError::Serialization(e.to_string()))?;
+        transactions
+            .entry(block_number)
+            .or_default()
+            .push(transaction);
+    }
+
+    for block_transactions in transactions.values_mut() {
+        block_transactions.sort_by_key(|tx| tx.transaction_index);
+    }
+    Ok(transactions)
+}
+
+/// Mapping of a transaction, shared by the nested block field and the flat transactions index
+fn transaction_properties() -> Value {
+    json!({
+        "hash": { "type": "keyword" },
+        "from": { "type": "keyword" },
+        "to": { "type": "keyword" },
+        "value": { "type": "keyword" },
+        "gas": { "type": "long" },
+        "gas_price": { "type": "keyword" },
+        "input": { "type": "text" },
+        "nonce": { "type": "long" },
+        "transaction_index": { "type": "long" },
+        "status": { "type": "integer" },
+        "gas_used": { "type": "long" },
+        "cumulative_gas_used": { "type": "long" },
+        "effective_gas_price": { "type": "keyword" },
+        "contract_address": { "type": "keyword" },
+        "logs_bloom": { "type": "keyword", "index": false },
+        "type": { "type": "integer" },
+        "max_fee_per_gas": { "type": "keyword" },
+        "max_priority_fee_per_gas": { "type": "keyword" },
+        "max_fee_per_blob_gas": { "type": "keyword" },
+        "access_list": {
+            "properties": {
+                "address": { "type": "keyword" },
+                "storage_keys": { "type": "keyword" }
+            }
+        },
+        "chain_id": { "type": "long" },
+        "blob_versioned_hashes": { "type": "keyword" }
+    })
 }
 
 impl ElasticsearchClient {
-    pub async fn new(config: &Config) -> Result<Self> {
+    pub fn new(config: &Config) -> Result<Self> {
         let mut url = config.es_url.clone();
         if !url.starts_with("http://") && !url.starts_with("https://") {
             url = format!("http://{}", url);
@@ -41,15 +210,269 @@ impl ElasticsearchClient {
         let transport = Transport::single_node(&final_url)?;
         let client = Elasticsearch::new(transport);
 
-        let es_client = ElasticsearchClient {
+        Ok(ElasticsearchClient {
             client,
             blocks_index: config.blocks_index(),
+            transactions_index: config.transactions_index(),
+            logs_index: config.logs_index(),
             meta_index: config.meta_index(),
+            transaction_storage: config.transaction_storage,
+            max_retries: config.es_max_retries,
+            index_logs: config.index_receipts && config.index_logs,
+        })
+    }
+
+    async fn create_index_if_missing(&self, index: &str, mapping: Value) -> Result<()> {
+        let exists = self
+            .client
+            .indices()
+            .exists(IndicesExistsParts::Index(&[index]))
+            .send()
+            .await?;
+
+        if !exists.status_code().is_success() {
+            self.client
+                .indices()
+                .create(IndicesCreateParts::Index(index))
+                .body(mapping)
+                .send()
+                .await?;
+            log::info!("Created index: {}", index);
+        }
+
+        Ok(())
+    }
+
+    /// Documents to write for `blocks`, according to the transaction storage and logs settings
+    fn bulk_items(&self, blocks: &[IndexedBlock]) -> Result<Vec<BulkItem>> {
+        let mut items = Vec::with_capacity(blocks.len());
+
+        for block in blocks {
+            let mut doc = serde_json::to_value(block)
+                .map_err(|e| IndexerError::Serialization(e.to_string()))?;
+            if !self.transaction_storage.nested() {
+                // Flat only: keep the block document free of the transactions array
+                if let Some(fields) = doc.as_object_mut() {
+                    fields.remove("transactions");
+                }
+            }
+            items.push(BulkItem {
+                block_number: block.number,
+                index: self.blocks_index.clone(),
+                id: block.number.to_string(),
+                doc,
+            });
+
+            if self.transaction_storage.flat() {
+                for tx in block.flat_transactions() {
+                    items.push(BulkItem {
+                        block_number: block.number,
+                        index: self.transactions_index.clone(),
+                        id: tx.transaction.hash.clone(),
+                        doc: serde_json::to_value(&tx)
+                            .map_err(|e| IndexerError::Serialization(e.to_string()))?,
+                    });
+                }
+            }
+
+            if self.index_logs {
+                for log in &block.logs {
+                    items.push(BulkItem {
+                        block_number: block.number,
+                        index: self.logs_index.clone(),
+                        id: log.doc_id(),
+                        doc: serde_json::to_value(log)
+                            .map_err(|e| IndexerError::Serialization(e.to_string()))?,
+                    });
+                }
+            }
+        }
+
+        Ok(items)
+    }
+
+    /// Send one bulk request and report the items that were not indexed
+    async fn send_bulk(&self, items: &[BulkItem]) -> Vec<BulkItemFailure> {
+        let ops: Vec<BulkOperation<&Value>> = items
+            .iter()
+            .map(|item| {
+                BulkOperation::index(&item.doc)
+                    .id(item.id.as_str())
+                    .index(item.index.as_str())
+                    .into()
+            })
+            .collect();
+
+        let response = match self.client.bulk(BulkParts::None).body(ops).send().await {
+            Ok(response) => response,
+            Err(e) => return all_failed(items.len(), 0, e.to_string()),
         };
 
-        es_client.create_indices().await?;
+        let status = response.status_code();
+        if !status.is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return all_failed(items.len(), status.as_u16(), text);
+        }
 
-        Ok(es_client)
+        match response.json::<Value>().await {
+            Ok(body) => bulk_failures(&body),
+            Err(e) => all_failed(items.len(), 0, e.to_string()),
+        }
+    }
+
+    /// Delete the documents of `index` whose `field` is above `block_number`
+    async fn delete_above(&self, index: &str, field: &str, block_number: u64) -> Result<u64> {
+        let query = json!({
+            "range": {
+                field: { "gt": block_number }
+            }
+        });
+        self.delete_matching(index, query).await
+    }
+
+    /// Delete the documents of `index` matching `query`. Documents changed while the query
+    /// ran are retried; any left after the retries fail the delete.
+    async fn delete_matching(&self, index: &str, query: Value) -> Result<u64> {
+        let mut deleted = 0;
+        let mut attempt = 0;
+
+        loop {
+            let response = self
+                .client
+                .delete_by_query(DeleteByQueryParts::Index(&[index]))
+                .conflicts(Conflicts::Proceed)
+                .refresh(true)
+                .body(json!({ "query": query }))
+                .send()
+                .await?
+                .error_for_status_code()?;
+
+            let body: Value = response.json().await?;
+            let (batch_deleted, conflicts) = delete_by_query_counts(&body)?;
+            deleted += batch_deleted;
+            if conflicts == 0 {
+                return Ok(deleted);
+            }
+            if attempt >= self.max_retries {
+                return Err(IndexerError::Elasticsearch(format!(
+                    "{} document(s) in {} kept changing while being deleted",
+                    conflicts, index
+                ))
+                .into());
+            }
+
+            attempt += 1;
+            let delay =
+                Duration::from_millis(500u64.saturating_mul(2u64.saturating_pow(attempt - 1)))
+                    .min(Duration::from_secs(30));
+            log::warn!(
+                "{} version conflict(s) deleting from {}, retrying in {:?} (attempt {}/{})",
+                conflicts,
+                index,
+                delay,
+                attempt,
+                self.max_retries
+            );
+            sleep(delay).await;
+        }
+    }
+
+    /// Load the flat transaction documents of `block_numbers`, which flat-only block documents
+    /// do not carry, paging through them with `search_after`
+    async fn get_flat_transactions(
+        &self,
+        block_numbers: &[u64],
+    ) -> Result<HashMap<u64, Vec<IndexedTransaction>>> {
+        let mut hits = Vec::new();
+        let mut search_after: Option<Value> = None;
+
+        loop {
+            let mut query = json!({
+                "size": FLAT_TRANSACTIONS_PAGE_SIZE,
+                "query": {
+                    "terms": { "block_number": block_numbers }
+                },
+                "sort": [
+                    { "block_number": "asc" },
+                    { "hash": "asc" }
+                ]
+            });
+            if let Some(after) = search_after.take() {
+                query["search_after"] = after;
+            }
+
+            let response = self
+                .client
+                .search(SearchParts::Index(&[&self.transactions_index]))
+                .body(query)
+                .send()
+                .await?;
+
+            if !response.status_code().is_success() {
+                let text = response.text().await.unwrap_or_default();
+                return Err(IndexerError::Elasticsearch(format!(
+                    "Search on {} failed: {}",
+                    self.transactions_index, text
+                ))
+                .into());
+            }
+
+            let body: Value = response.json().await?;
+            let page = body["hits"]["hits"].as_array().cloned().unwrap_or_default();
+            let full = page.len() == FLAT_TRANSACTIONS_PAGE_SIZE;
+            search_after = page.last().map(|hit| hit["sort"].clone());
+            hits.extend(page);
+            if !full {
+                break;
+            }
+        }
+
+        transactions_by_block(&hits)
+    }
+
+    async fn get_last_indexed_block(&self) -> Result<u64> {
+        let response = self
+            .client
+            .get(GetParts::IndexId(&self.meta_index, "checkpoint"))
+            .send()
+            .await?;
+
+        // Any other failure must not be mistaken for a fresh start
+        if response.status_code().as_u16() != 404 {
+            let body: Value = response.error_for_status_code()?.json().await?;
+            if body["found"].as_bool().unwrap_or(false) {
+                let block = body["_source"]["last_indexed_block"].as_u64().unwrap_or(0);
+                log::debug!("Retrieved checkpoint from Elasticsearch: block {}", block);
+                return Ok(block);
+            }
+        }
+
+        log::info!("No checkpoint found in Elasticsearch, starting from block 0");
+        Ok(0)
+    }
+
+    async fn set_checkpoint(&self, block_number: u64) -> Result<()> {
+        let body = json!({
+            "last_indexed_block": block_number,
+            "updated_at": chrono::Utc::now().timestamp_millis()
+        });
+
+        self.client
+            .index(IndexParts::IndexId(&self.meta_index, "checkpoint"))
+            .body(body)
+            .send()
+            .await?
+            .error_for_status_code()?;
+
+        log::debug!("Checkpoint saved to Elasticsearch: block {}", block_number);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for ElasticsearchClient {
+    fn name(&self) -> String {
+        "Elasticsearch".to_string()
     }
 
     async fn create_indices(&self) -> Result<()> {
@@ -69,21 +492,25 @@ impl ElasticsearchClient {
                     "size": { "type": "long" },
                     "transactions": {
                         "type": "nested",
-                        "properties": {
-                            "hash": { "type": "keyword" },
-                            "from": { "type": "keyword" },
-                            "to": { "type": "keyword" },
-                            "value": { "type": "keyword" },
-                            "gas": { "type": "long" },
-                            "gas_price": { "type": "keyword" },
-                            "input": { "type": "text" },
-                            "nonce": { "type": "long" },
-                            "transaction_index": { "type": "long" }
-                        }
+                        "properties": transaction_properties()
                     },
                     "transaction_count": { "type": "integer" },
                     "uncles": { "type": "integer" },
-                    "indexed_at": { "type": "long" }
+                    "indexed_at": { "type": "long" },
+                    "finality": { "type": "keyword" },
+                    "base_fee_per_gas": { "type": "keyword" },
+                    "withdrawals_root": { "type": "keyword" },
+                    "blob_gas_used": { "type": "long" },
+                    "excess_blob_gas": { "type": "long" },
+                    "withdrawals": {
+                        "type": "nested",
+                        "properties": {
+                            "index": { "type": "long" },
+                            "validator_index": { "type": "long" },
+                            "address": { "type": "keyword" },
+                            "amount": { "type": "long" }
+                        }
+                    }
                 }
             },
             "settings": {
@@ -91,22 +518,57 @@ impl ElasticsearchClient {
                 "number_of_replicas": 0
             }
         });
-
-        let exists = self
-            .client
-            .indices()
-            .exists(IndicesExistsParts::Index(&[&self.blocks_index]))
-            .send()
+        self.create_index_if_missing(&self.blocks_index, blocks_mapping)
             .await?;
 
-        if !exists.status_code().is_success() {
-            self.client
-                .indices()
-                .create(IndicesCreateParts::Index(&self.blocks_index))
-                .body(blocks_mapping)
-                .send()
+        // Create flat transactions index, one document per transaction
+        if self.transaction_storage.flat() {
+            let mut properties = transaction_properties();
+            properties["block_number"] = json!({ "type": "long" });
+            properties["block_hash"] = json!({ "type": "keyword" });
+            properties["timestamp"] = json!({ "type": "long" });
+            properties["miner"] = json!({ "type": "keyword" });
+
+            let transactions_mapping = json!({
+                "mappings": {
+                    "properties": properties
+                },
+                "settings": {
+                    "number_of_shards": 1,
+                    "number_of_replicas": 0
+                }
+            });
+            self.create_index_if_missing(&self.transactions_index, transactions_mapping)
+                .await?;
+        }
+
+        // Create logs index, tuned for address/topic keyword filters
+        if self.index_logs {
+            let logs_mapping = json!({
+                "mappings": {
+                    "properties": {
+                        "address": { "type": "keyword" },
+                        "topic0": { "type": "keyword" },
+                        "topic1": { "type": "keyword" },
+                        "topic2": { "type": "keyword" },
+                        "topic3": { "type": "keyword" },
+                        "data": { "type": "text", "index": false },
+                        "log_index": { "type": "long" },
+                        "transaction_hash": { "type": "keyword" },
+                        "transaction_index": { "type": "long" },
+                        "block_number": { "type": "long" },
+                        "block_hash": { "type": "keyword" },
+                        "timestamp": { "type": "long" },
+                        "removed": { "type": "boolean" }
+                    }
+                },
+                "settings": {
+                    "number_of_shards": 1,
+                    "number_of_replicas": 0
+                }
+            });
+            self.create_index_if_missing(&self.logs_index, logs_mapping)
                 .await?;
-            log::info!("Created index: {}", self.blocks_index);
         }
 
         // Create meta index for checkpoint
@@ -114,109 +576,216 @@ impl ElasticsearchClient {
             "mappings": {
                 "properties": {
                     "last_indexed_block": { "type": "long" },
+                    "scanned_through": { "type": "long" },
+                    "failed_blocks": { "type": "long" },
                     "updated_at": { "type": "long" }
                 }
             }
         });
-
-        let exists = self
-            .client
-            .indices()
-            .exists(IndicesExistsParts::Index(&[&self.meta_index]))
-            .send()
+        self.create_index_if_missing(&self.meta_index, meta_mapping)
             .await?;
 
-        if !exists.status_code().is_success() {
-            self.client
-                .indices()
-                .create(IndicesCreateParts::Index(&self.meta_index))
-                .body(meta_mapping)
-                .send()
-                .await?;
-            log::info!("Created index: {}", self.meta_index);
-        }
-
         Ok(())
     }
 
-    pub async fn index_block(&self, block: &IndexedBlock) -> Result<()> {
-        let body =
-            serde_json::to_string(block).map_err(|e| IndexerError::Serialization(e.to_string()))?;
+    async fn ping(&self) -> bool {
+        match self.client.ping().send().await {
+            Ok(response) => response.status_code().is_success(),
+            Err(_) => false,
+        }
+    }
 
-        self.client
-            .index(IndexParts::IndexId(
-                &self.blocks_index,
-                &block.number.to_string(),
+    /// Write blocks, plus their flat transactions and logs when enabled, in a single bulk request.
+    ///
+    /// Items rejected with a retryable status are resent with exponential backoff; any item
+    /// that still fails makes the call return an error listing the affected block numbers.
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        if blocks.is_empty() {
+            return Ok(());
+        }
+
+        let mut pending = self.bulk_items(blocks)?;
+        let mut rejected: BTreeMap<u64, String> = BTreeMap::new();
+        let mut attempt = 0;
+
+        loop {
+            let timer = METRICS.es_bulk_duration.start_timer();
+            let failures = self.send_bulk(&pending).await;
+            timer.observe_duration();
+            METRICS.es_bulk_item_failures.inc_by(failures.len() as u64);
+
+            let mut retry = HashSet::new();
+            for failure in failures {
+                if is_retryable_status(failure.status) && attempt < self.max_retries {
+                    retry.insert(failure.position);
+                } else {
+                    rejected
+                        .entry(pending[failure.position].block_number)
+                        .or_insert_with(|| format!("{} ({})", failure.reason, failure.status));
+                }
+            }
+
+            if retry.is_empty() {
+                break;
+            }
+
+            attempt += 1;
+            METRICS.es_bulk_retries.inc();
+            let delay =
+                Duration::from_millis(500u64.saturating_mul(2u64.saturating_pow(attempt - 1)))
+                    .min(Duration::from_secs(30));
+            log::warn!(
+                "Retrying {} rejected bulk item(s) in {:?} (attempt {}/{})",
+                retry.len(),
+                delay,
+                attempt,
+                self.max_retries
+            );
+            sleep(delay).await;
+
+            pending = pending
+                .into_iter()
+                .enumerate()
+                .filter(|(position, _)| retry.contains(position))
+                .map(|(_, item)| item)
+                .collect();
+        }
+
+        if let Some(reason) = rejected.values().next() {
+            let block_numbers: Vec<u64> = rejected.keys().copied().collect();
+            return Err(IndexerError::Elasticsearch(format!(
+                "Bulk indexing failed for block(s) {:?}: {}",
+                block_numbers, reason
             ))
-            .body(body)
-            .send()
-            .await?;
+            .into());
+        }
 
         Ok(())
     }
 
-    pub async fn bulk_index_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
-        if blocks.is_empty() {
-            return Ok(());
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        let deleted = self
+            .delete_above(&self.blocks_index, "number", block_number)
+            .await?;
+
+        if self.transaction_storage.flat() {
+            self.delete_above(&self.transactions_index, "block_number", block_number)
+                .await?;
         }
 
-        let mut ops: Vec<BulkOperation<IndexedBlock>> = Vec::with_capacity(blocks.len());
+        if self.index_logs {
+            self.delete_above(&self.logs_index, "block_number", block_number)
+                .await?;
+        }
 
-        for block in blocks {
-            ops.push(
-                BulkOperation::index(block.clone())
-                    .id(block.number.to_string())
-                    .into(),
-            );
+        log::debug!(
+            "Deleted {} block(s) above {} from Elasticsearch",
+            deleted,
+            block_number
+        );
+        Ok(deleted)
+    }
+
+    async fn delete_block_children(&self, block_numbers: &[u64]) -> Result<()> {
+        let query = json!({
+            "terms": { "block_number": block_numbers }
+        });
+
+        if self.transaction_storage.flat() {
+            self.delete_matching(&self.transactions_index, query.clone())
+                .await?;
+        }
+        if self.index_logs {
+            self.delete_matching(&self.logs_index, query).await?;
         }
+        Ok(())
+    }
 
-        self.client
-            .bulk(BulkParts::Index(&self.blocks_index))
-            .body(ops)
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64> {
+        let settled: &[&str] = match status {
+            FinalityStatus::Unfinalized => return Ok(0),
+            FinalityStatus::Safe => &["safe", "finalized"],
+            FinalityStatus::Finalized => &["finalized"],
+        };
+
+        let response = self
+            .client
+            .update_by_query(UpdateByQueryParts::Index(&[&self.blocks_index]))
+            .conflicts(Conflicts::Proceed)
+            .refresh(true)
+            .body(json!({
+                "query": {
+                    "bool": {
+                        "filter": [
+                            { "range": { "number": { "lte": block_number } } }
+                        ],
+                        "must_not": [
+                            { "terms": { "finality": settled } }
+                        ]
+                    }
+                },
+                "script": {
+                    "source": "ctx._source.finality = params.status",
+                    "lang": "painless",
+                    "params": { "status": status.as_str() }
+                }
+            }))
             .send()
             .await?;
 
-        Ok(())
+        // An error keeps the finality mark from moving past blocks that were not updated
+        let body: Value = response.error_for_status_code()?.json().await?;
+        let updated = update_by_query_count(&body)?;
+        log::debug!(
+            "Marked {} block(s) up to {} as {}",
+            updated,
+            block_number,
+            status.as_str()
+        );
+        Ok(updated)
     }
 
-    pub async fn get_last_indexed_block(&self) -> Result<u64> {
+    /// Load the sync progress, deriving it from the checkpoint for indices that predate it
+    async fn get_progress(&self) -> Result<SyncProgress> {
         let response = self
             .client
-            .get(GetParts::IndexId(&self.meta_index, "checkpoint"))
+            .get(GetParts::IndexId(&self.meta_index, "progress"))
             .send()
-            .await;
+            .await?;
 
-        match response {
-            Ok(res) => {
-                let body: Value = res.json().await?;
-                let block = body["_source"]["last_indexed_block"].as_u64().unwrap_or(0);
-                log::debug!("Retrieved checkpoint from Elasticsearch: block {}", block);
-                Ok(block)
-            }
-            Err(_) => {
-                log::info!("No checkpoint found in Elasticsearch, starting from block 0");
-                Ok(0)
+        // Falling back on anything but a missing document would drop the failed blocks
+        if response.status_code().as_u16() != 404 {
+            let body: Value = response.error_for_status_code()?.json().await?;
+            if body["found"].as_bool().unwrap_or(false) {
+                let progress = serde_json::from_value(body["_source"].clone())
+                    .map_err(|e| IndexerError::Serialization(e.to_string()))?;
+                return Ok(progress);
             }
         }
+
+        let checkpoint = self.get_last_indexed_block().await?;
+        Ok(SyncProgress::from_checkpoint(checkpoint))
     }
 
-    pub async fn set_checkpoint(&self, block_number: u64) -> Result<()> {
+    /// Persist the sync progress, then the checkpoint derived from it
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
         let body = json!({
-            "last_indexed_block": block_number,
+            "scanned_through": progress.scanned_through,
+            "failed_blocks": progress.failed_blocks,
             "updated_at": chrono::Utc::now().timestamp_millis()
         });
 
         self.client
-            .index(IndexParts::IndexId(&self.meta_index, "checkpoint"))
+            .index(IndexParts::IndexId(&self.meta_index, "progress"))
             .body(body)
             .send()
-            .await?;
+            .await?
+            .error_for_status_code()?;
 
-        log::debug!("Checkpoint saved to Elasticsearch: block {}", block_number);
-        Ok(())
+        self.set_checkpoint(progress.checkpoint()).await
     }
 
-    pub async fn refresh_blocks_index(&self) -> Result<()> {
+    async fn refresh(&self) -> Result<()> {
         self.client
             .indices()
             .refresh(IndicesRefreshParts::Index(&[&self.blocks_index]))
@@ -224,4 +793,250 @@ impl ElasticsearchClient {
             .await?;
         Ok(())
     }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        let response = self
+            .client
+            .get(GetParts::IndexId(
+                &self.blocks_index,
+                &block_number.to_string(),
+            ))
+            .send()
+            .await?;
+
+        if response.status_code().as_u16() == 404 {
+            return Ok(None);
+        }
+
+        // A failed lookup is not a missing block, which would skip the reorg checks
+        let body: Value = response.error_for_status_code()?.json().await?;
+        if !body["found"].as_bool().unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let block = serde_json::from_value(body["_source"].clone())
+            .map_err(|e| IndexerError::Serialization(e.to_string()))?;
+        Ok(Some(block))
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        let mut blocks = HashMap::with_capacity(block_numbers.len());
+
+        for chunk in block_numbers.chunks(1000) {
+            let response = self
+                .client
+                .search(SearchParts::Index(&[&self.blocks_index]))
+                .body(json!({
+                    "size": chunk.len(),
+                    "query": {
+                        "terms": { "number": chunk }
+                    }
+                }))
+                .send()
+                .await?;
+
+            if !response.status_code().is_success() {
+                let text = response.text().await.unwrap_or_default();
+                return Err(IndexerError::Elasticsearch(format!(
+                    "Search on {} failed: {}",
+                    self.blocks_index, text
+                ))
+                .into());
+            }
+
+            let body: Value = response.json().await?;
+            for hit in body["hits"]["hits"].as_array().into_iter().flatten() {
+                let block: IndexedBlock = serde_json::from_value(hit["_source"].clone())
+                    .map_err(|e| IndexerError::Serialization(e.to_string()))?;
+                blocks.insert(block.number, block);
+            }
+        }
+
+        // Flat-only block documents have no transactions; load them so they can be compared
+        if !self.transaction_storage.nested() && !blocks.is_empty() {
+            let block_numbers: Vec<u64> = blocks.keys().copied().collect();
+            for chunk in block_numbers.chunks(1000) {
+                for (block_number, transactions) in self.get_flat_transactions(chunk).await? {
+                    if let Some(block) = blocks.get_mut(&block_number) {
+                        block.transactions = transactions;
+                    }
+                }
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        let response = self
+            .client
+            .search(SearchParts::Index(&[&self.blocks_index]))
+            .body(json!({
+                "size": 0,
+                "query": {
+                    "range": {
+                        "number": { "gte": from, "lte": to }
+                    }
+                },
+                "aggs": {
+                    "blocks": {
+                        "histogram": {
+                            "field": "number",
+                            "interval": interval,
+                            "min_doc_count": 0,
+                            "extended_bounds": { "min": from, "max": to }
+                        }
+                    }
+                }
+            }))
+            .send()
+            .await?;
+
+        if !response.status_code().is_success() {
+            let text = response.text().await.unwrap_or_default();
+            return Err(IndexerError::Elasticsearch(format!(
+                "Histogram query on {} failed: {}",
+                self.blocks_index, text
+            ))
+            .into());
+        }
+
+        let body: Value = response.json().await?;
+        let buckets = body["aggregations"]["blocks"]["buckets"]
+            .as_array()
+            .map(|buckets| {
+                buckets
+                    .iter()
+                    .map(|bucket| {
+                        (
+                            bucket["key"].as_f64().unwrap_or(0.0) as u64,
+                            bucket["doc_count"].as_u64().unwrap_or(0),
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(buckets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bulk_failures_without_errors() {
+        let body = json!({
+            "errors": false,
+            "items": [
+                { "index": { "_id": "1", "status": 201 } }
+            ]
+        });
+
+        assert!(bulk_failures(&body).is_empty());
+    }
+
+    #[test]
+    fn test_bulk_failures_reports_rejected_items() {
+        let body = json!({
+            "errors": true,
+            "items": [
+                { "index": { "_id": "1", "status": 201 } },
+                { "index": { "_id": "2", "status": 429, "error": { "type": "es_rejected_execution_exception", "reason": "rejected execution" } } },
+                { "index": { "_id": "3", "status": 400, "error": { "type": "mapper_parsing_exception", "reason": "failed to parse field [gas]" } } }
+            ]
+        });
+
+        let failures = bulk_failures(&body);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(
+            failures[0],
+            BulkItemFailure {
+                position: 1,
+                status: 429,
+                reason: "rejected execution".to_string(),
+            }
+        );
+        assert_eq!(failures[1].position, 2);
+        assert_eq!(failures[1].status, 400);
+    }
+
+    #[test]
+    fn test_transactions_by_block() {
+        let hit = |block_number: u64, hash: &str, index: u64| {
+            json!({
+                "_source": {
+                    "hash": hash,
+                    "from": "0xfrom",
+                    "to": null,
+                    "value": "0",
+                    "gas": 21000,
+                    "gas_price": "1",
+                    "input": "0x",
+                    "nonce": 0,
+                    "transaction_index": index,
+                    "block_number": block_number,
+                    "block_hash": "0xblock",
+                    "timestamp": 0,
+                    "miner": null
+                },
+                "sort": [block_number, hash]
+            })
+        };
+        let hits = vec![hit(10, "0xb", 0), hit(10, "0xa", 1), hit(11, "0xc", 0)];
+
+        let transactions = transactions_by_block(&hits).unwrap();
+        let hashes: Vec<&str> = transactions[&10]
+            .iter()
+            .map(|tx| tx.hash.as_str())
+            .collect();
+        assert_eq!(hashes, vec!["0xb", "0xa"]);
+        assert_eq!(transactions[&11].len(), 1);
+    }
+
+    #[test]
+    fn test_delete_by_query_counts() {
+        let body = json!({ "deleted": 3, "version_conflicts": 1, "failures": [] });
+        assert_eq!(delete_by_query_counts(&body).unwrap(), (3, 1));
+
+        let body = json!({
+            "deleted": 0,
+            "version_conflicts": 0,
+            "failures": [
+                { "index": "workqueue_blocks", "cause": { "type": "cluster_block_exception" } }
+            ]
+        });
+        let error = delete_by_query_counts(&body).unwrap_err().to_string();
+        assert!(error.contains("cluster_block_exception"));
+    }
+
+    #[test]
+    fn test_update_by_query_count() {
+        let body = json!({ "updated": 5, "version_conflicts": 0, "failures": [] });
+        assert_eq!(update_by_query_count(&body).unwrap(), 5);
+
+        let body = json!({
+            "updated": 2,
+            "failures": [
+                { "index": "workqueue-blocks", "cause": { "type": "es_rejected_execution_exception" } }
+            ]
+        });
+        let error = update_by_query_count(&body).unwrap_err().to_string();
+        assert!(error.contains("es_rejected_execution_exception"));
+    }
+
+    #[test]
+    fn test_retryable_statuses() {
+        assert!(is_retryable_status(429));
+        assert!(is_retryable_status(503));
+        assert!(is_retryable_status(0));
+        assert!(!is_retryable_status(400));
+        assert!(!is_retryable_status(409));
+    }
 }
@@ -1,22 +1,127 @@
+use ethers::providers::{
+    HttpClientError, HttpRateLimitRetryPolicy, ProviderError, RetryPolicy, RpcError,
+};
 use thiserror::Error;
 
+use crate::transport::TransportError;
+
 #[derive(Error, Debug)]
 pub enum IndexerError {
     #[error("Elasticsearch error: {0}")]
-    #[allow(dead_code)]
     Elasticsearch(String),
 
+    /// Transient node failure: timeouts, connection errors, 5xx, rate limiting
     #[error("RPC error: {0}")]
-    #[allow(dead_code)]
     Rpc(String),
 
+    /// The node answered with a JSON-RPC error that retrying will not fix
+    #[error("RPC request rejected ({code}): {message}")]
+    RpcRejected { code: i64, message: String },
+
+    #[error("Block {0} not found on the RPC node")]
+    BlockNotFound(u64),
+
+    /// The node answered, but not with the data that was asked for
+    #[error("Invalid RPC response: {0}")]
+    InvalidResponse(String),
+
     #[error("Serialization error: {0}")]
     Serialization(String),
 }
 
+impl IndexerError {
+    /// Whether retrying the same request may succeed. Missing blocks are retried because
+    /// load-balanced nodes briefly lag behind the head they report.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, IndexerError::Rpc(_) | IndexerError::BlockNotFound(_))
+    }
+
+    /// Short name of the variant, e.g. for log fields and metric labels
+    pub fn kind(&self) -> &'static str {
+        match self {
+            IndexerError::Elasticsearch(_) => "elasticsearch",
+            IndexerError::Rpc(_) => "rpc",
+            IndexerError::RpcRejected { .. } => "rpc_rejected",
+            IndexerError::BlockNotFound(_) => "block_not_found",
+            IndexerError::InvalidResponse(_) => "invalid_response",
+            IndexerError::Serialization(_) => "serialization",
+        }
+    }
+}
+
+fn is_transient_http(error: &HttpClientError) -> bool {
+    match error {
+        HttpClientError::ReqwestError(_) => true,
+        // Proxies and gateways answer outages with non-JSON error pages
+        HttpClientError::SerdeJson { text, .. } => {
+            serde_json::from_str::<serde::de::IgnoredAny>(text).is_err()
+                || HttpRateLimitRetryPolicy.should_retry(error)
+        }
+        // Rate limits and "header not found" just after the head
+        HttpClientError::JsonRpcError(_) => HttpRateLimitRetryPolicy.should_retry(error),
+    }
+}
+
+impl From<ProviderError> for IndexerError {
+    fn from(error: ProviderError) -> Self {
+        let transient = match &error {
+            ProviderError::HTTPError(_) => true,
+            ProviderError::JsonRpcClientError(inner) => {
+                let inner: &(dyn std::error::Error + 'static) = &**inner;
+                match inner.downcast_ref::<TransportError>() {
+                    Some(TransportError::Http(e)) => is_transient_http(e),
+                    Some(TransportError::Timeout(_)) => true,
+                    Some(TransportError::Status(status)) => *status == 429 || *status >= 500,
+                    None => inner
+                        .downcast_ref::<HttpClientError>()
+                        .is_some_and(is_transient_http),
+                }
+            }
+            _ => false,
+        };
+
+        if transient {
+            return IndexerError::Rpc(error.to_string());
+        }
+
+        match error.as_error_response() {
+            Some(response) => IndexerError::RpcRejected {
+                code: response.code,
+                message: response.message.clone(),
+            },
+            None => IndexerError::InvalidResponse(error.to_string()),
+        }
+    }
+}
+
+impl From<TransportError> for IndexerError {
+    fn from(error: TransportError) -> Self {
+        ProviderError::from(error).into()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ethers::providers::JsonRpcError;
+    use std::time::Duration;
+
+    fn json_rpc_error(code: i64, message: &str) -> ProviderError {
+        let error = HttpClientError::JsonRpcError(JsonRpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        });
+        TransportError::Http(error).into()
+    }
+
+    fn serde_error(text: &str) -> ProviderError {
+        let error = HttpClientError::SerdeJson {
+            err: serde_json::from_str::<u64>("x").unwrap_err(),
+            text: text.to_string(),
+        };
+        TransportError::Http(error).into()
+    }
 
     #[test]
     fn test_elasticsearch_error() {
@@ -41,4 +146,39 @@ mod tests {
         assert!(error_msg.contains("Serialization error"));
         assert!(error_msg.contains("Invalid JSON"));
     }
+
+    #[test]
+    fn test_transient_rpc_errors() {
+        let errors = [
+            TransportError::Timeout(Duration::from_secs(30)).into(),
+            TransportError::Status(429).into(),
+            TransportError::Status(503).into(),
+            IndexerError::from(json_rpc_error(-32000, "header not found")),
+            IndexerError::from(json_rpc_error(429, "Too many requests")),
+            IndexerError::from(serde_error("<html>502 Bad Gateway</html>")),
+        ];
+
+        for error in errors {
+            assert!(matches!(error, IndexerError::Rpc(_)), "{:?}", error);
+            assert!(error.is_transient());
+        }
+    }
+
+    #[test]
+    fn test_permanent_rpc_errors() {
+        let rejected = IndexerError::from(json_rpc_error(-32601, "method not found"));
+        assert!(matches!(
+            rejected,
+            IndexerError::RpcRejected { code: -32601, .. }
+        ));
+        assert!(!rejected.is_transient());
+        assert_eq!(rejected.kind(), "rpc_rejected");
+
+        let invalid = IndexerError::from(serde_error(r#"{"unexpected":true}"#));
+        assert!(matches!(invalid, IndexerError::InvalidResponse(_)));
+        assert!(!invalid.is_transient());
+
+        assert!(!IndexerError::Elasticsearch("down".to_string()).is_transient());
+        assert!(IndexerError::BlockNotFound(10).is_transient());
+    }
 }
@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+/// Maximum number of histogram buckets requested per gap detection query
+const MAX_BUCKETS: u64 = 1000;
+
+/// Histogram interval that covers `from..=to` in at most `MAX_BUCKETS` buckets
+pub fn histogram_interval(from: u64, to: u64) -> u64 {
+    let span = to - from + 1;
+    span.div_ceil(MAX_BUCKETS).max(1)
+}
+
+/// Block range covered by the histogram bucket starting at `key`, clamped to `from..=to`
+pub fn bucket_bounds(key: u64, interval: u64, from: u64, to: u64) -> (u64, u64) {
+    let start = key.max(from);
+    let end = key.saturating_add(interval - 1).min(to);
+    (start, end)
+}
+
+/// Histogram buckets of `interval` block numbers covering `from..=to`, keyed by their first
+/// block number, with the block counts of `counts` and zero for the buckets it lacks
+pub fn fill_buckets(
+    from: u64,
+    to: u64,
+    interval: u64,
+    counts: &HashMap<u64, u64>,
+) -> Vec<(u64, u64)> {
+    let first = from - from % interval;
+    (first..=to)
+        .step_by(interval as usize)
+        .map(|key| (key, counts.get(&key).copied().unwrap_or(0)))
+        .collect()
+}
+
+/// Sort ranges and merge the ones that overlap or touch
+pub fn merge_ranges(mut ranges: Vec<(u64, u64)>) -> Vec<(u64, u64)> {
+    ranges.sort_unstable();
+
+    let mut merged: Vec<(u64, u64)> = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+
+    merged
+}
+
+/// Number of blocks covered by a set of inclusive ranges
+pub fn count_blocks(ranges: &[(u64, u64)]) -> u64 {
+    ranges.iter().map(|(start, end)| end - start + 1).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram_interval() {
+        assert_eq!(histogram_interval(0, 999), 1);
+        assert_eq!(histogram_interval(0, 1000), 2);
+        assert_eq!(histogram_interval(100, 100), 1);
+        assert_eq!(histogram_interval(0, 19_999_999), 20_000);
+    }
+
+    #[test]
+    fn test_bucket_bounds_are_clamped() {
+        assert_eq!(bucket_bounds(0, 100, 50, 1000), (50, 99));
+        assert_eq!(bucket_bounds(100, 100, 50, 1000), (100, 199));
+        assert_eq!(bucket_bounds(1000, 100, 50, 1000), (1000, 1000));
+        assert_eq!(bucket_bounds(7, 1, 0, 10), (7, 7));
+    }
+
+    #[test]
+    fn test_fill_buckets() {
+        let counts = HashMap::from([(100, 40), (300, 2)]);
+        assert_eq!(
+            fill_buckets(150, 399, 100, &counts),
+            vec![(100, 40), (200, 0), (300, 2)]
+        );
+        assert_eq!(fill_buckets(5, 5, 1, &HashMap::new()), vec![(5, 0)]);
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let ranges = vec![(10, 20), (1, 3), (4, 5), (21, 21), (30, 40), (35, 36)];
+        assert_eq!(merge_ranges(ranges), vec![(1, 5), (10, 21), (30, 40)]);
+        assert!(merge_ranges(vec![]).is_empty());
+    }
+
+    #[test]
+    fn test_count_blocks() {
+        assert_eq!(count_blocks(&[(1, 5), (10, 10)]), 6);
+        assert_eq!(count_blocks(&[]), 0);
+    }
+}
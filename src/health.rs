@@ -0,0 +1,87 @@
+use serde::Serialize;
+
+/// Indexing state reported by `/healthz` and `/readyz`
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncState {
+    /// Every block up to the checkpoint is indexed
+    pub checkpoint: u64,
+    /// Highest head reported by the RPC nodes
+    pub head_block: Option<u64>,
+    /// Unix timestamp, in seconds, of the last successful write
+    pub last_indexed_at: Option<u64>,
+}
+
+impl SyncState {
+    pub fn lag_blocks(&self) -> Option<u64> {
+        self.head_block
+            .map(|head| head.saturating_sub(self.checkpoint))
+    }
+}
+
+/// Whether the indexer can do its job: both backends answer and it keeps up with the chain
+#[derive(Debug, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    pub rpc_reachable: bool,
+    pub sink_reachable: bool,
+    pub lag_blocks: Option<u64>,
+    pub max_lag_blocks: u64,
+    #[serde(flatten)]
+    pub state: SyncState,
+}
+
+impl Readiness {
+    pub fn new(
+        state: SyncState,
+        rpc_reachable: bool,
+        sink_reachable: bool,
+        max_lag_blocks: u64,
+    ) -> Self {
+        let lag_blocks = state.lag_blocks();
+        // Without a known head the lag cannot be vouched for
+        let ready =
+            rpc_reachable && sink_reachable && lag_blocks.is_some_and(|lag| lag <= max_lag_blocks);
+
+        Readiness {
+            ready,
+            rpc_reachable,
+            sink_reachable,
+            lag_blocks,
+            max_lag_blocks,
+            state,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(checkpoint: u64, head_block: Option<u64>) -> SyncState {
+        SyncState {
+            checkpoint,
+            head_block,
+            last_indexed_at: Some(1_700_000_000),
+        }
+    }
+
+    #[test]
+    fn test_ready_when_backends_answer_and_lag_is_low() {
+        let readiness = Readiness::new(state(990, Some(1_000)), true, true, 10);
+        assert!(readiness.ready);
+        assert_eq!(readiness.lag_blocks, Some(10));
+
+        let json = serde_json::to_value(&readiness).unwrap();
+        assert_eq!(json["checkpoint"], 990);
+        assert_eq!(json["head_block"], 1_000);
+        assert_eq!(json["last_indexed_at"], 1_700_000_000);
+    }
+
+    #[test]
+    fn test_not_ready() {
+        assert!(!Readiness::new(state(900, Some(1_000)), true, true, 10).ready);
+        assert!(!Readiness::new(state(1_000, None), true, true, 10).ready);
+        assert!(!Readiness::new(state(1_000, Some(1_000)), false, true, 10).ready);
+        assert!(!Readiness::new(state(1_000, Some(1_000)), true, false, 10).ready);
+    }
+}
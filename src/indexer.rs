@@ -1,66 +1,216 @@
 use anyhow::{Context, Result};
 use ethers::middleware::Middleware;
-use ethers::providers::{Http, Provider};
-use ethers::types::{Block, Transaction, U256};
+use ethers::providers::{Provider, Ws};
+use ethers::types::{Block, BlockNumber, Log, Transaction, TransactionReceipt, H256, U256, U64};
 use futures::stream::{self, StreamExt};
 use log::{debug, error, info, warn};
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::Semaphore;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use tokio::time::{sleep, Duration};
 
-use crate::config::Config;
-use crate::elasticsearch::ElasticsearchClient;
-use crate::models::{IndexedBlock, IndexedTransaction};
+use crate::config::{Config, FinalityMode};
+use crate::error::IndexerError;
+use crate::gaps;
+use crate::health::{Readiness, SyncState};
+use crate::metrics::METRICS;
+use crate::models::{
+    FinalityStatus, IndexedAccessListItem, IndexedBlock, IndexedLog, IndexedTransaction,
+    IndexedWithdrawal,
+};
+use crate::progress::SyncProgress;
+use crate::rpc::{self, RpcEndpoint, RpcPool};
+use crate::shutdown::Shutdown;
+use crate::sink::{self, Sink};
+use crate::throttle::AdaptiveConcurrency;
+use crate::verify::{self, ChainBlock, VerifyOptions};
+
+/// A newHeads subscription without a notification for this long is treated as dropped
+const WS_HEAD_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long live sync polls over HTTP before trying to reconnect the WebSocket
+const WS_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+/// How long `/readyz` waits for the sink to answer a ping
+const READY_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct BlockIndexer {
-    provider: Arc<Provider<Http>>,
-    es_client: ElasticsearchClient,
+    rpc: Arc<RpcPool>,
+    /// Number of blocks fetched concurrently, adapted to how the RPC endpoints cope
+    concurrency: Arc<AdaptiveConcurrency>,
+    sink: Box<dyn Sink>,
     config: Config,
+    /// Highest safe block already promoted in the sink
+    safe_marked: AtomicU64,
+    /// Highest finalized block already promoted in the sink
+    finalized_marked: AtomicU64,
+    /// Checkpoint of the running sync, reported by the health endpoints
+    checkpoint: AtomicU64,
+    /// Unix timestamp of the last successful write; 0 before the first one
+    last_indexed_at: AtomicU64,
+    shutdown: Shutdown,
+    started_at: Instant,
 }
 
 impl BlockIndexer {
     pub async fn new(config: Config) -> Result<Self> {
+        let sink = sink::connect(&config).await?;
+        Self::with_sink(config, sink).await
+    }
+
+    /// Create an indexer writing to `sink`, whose indices must already exist
+    pub async fn with_sink(config: Config, sink: Box<dyn Sink>) -> Result<Self> {
         info!("Initializing Blockchain Indexer...");
-        info!("  RPC URL: {}", config.rpc_url);
-        info!("  Elasticsearch URL: {}", config.es_url);
+        for url in config.rpc_endpoints() {
+            info!("  RPC URL: {}", url);
+        }
+        if let Some(ws_url) = &config.rpc_ws_url {
+            info!("  RPC WS URL: {}", ws_url);
+        }
+        info!("  Sink: {}", sink.name());
         info!("  Index Prefix: {}", config.index_prefix);
         info!("  Batch Size: {}", config.batch_size);
         info!("  Start Block: {}", config.start_block);
-        info!("  Concurrency: {}", config.concurrency);
+        info!(
+            "  Concurrency: {} (max {})",
+            config.concurrency, config.max_concurrency
+        );
+        if let Some(rate) = config.rpc_rate_limit {
+            info!("  RPC Rate Limit: {} units/s per endpoint", rate);
+        }
         info!("  ES Bulk Size: {}", config.es_bulk_size);
+        info!("  Finality Mode: {:?}", config.finality_mode);
+        info!("  Index Receipts: {}", config.index_receipts);
 
-        let provider = Arc::new(
-            Provider::<Http>::try_from(&config.rpc_url).context("Failed to create RPC provider")?,
-        );
+        let concurrency = Arc::new(AdaptiveConcurrency::new(
+            config.concurrency,
+            1,
+            config.max_concurrency,
+        ));
+        let rpc = Arc::new(RpcPool::new(&config, &concurrency)?);
+        rpc.refresh_heads().await;
+        rpc.spawn_head_monitor();
 
         info!("Connected to RPC provider successfully");
 
-        let es_client = ElasticsearchClient::new(&config).await?;
-
-        info!("Connected to Elasticsearch successfully");
-
         Ok(BlockIndexer {
-            provider,
-            es_client,
+            rpc,
+            concurrency,
+            sink,
             config,
+            safe_marked: AtomicU64::new(0),
+            finalized_marked: AtomicU64::new(0),
+            checkpoint: AtomicU64::new(0),
+            last_indexed_at: AtomicU64::new(0),
+            shutdown: Shutdown::default(),
+            started_at: Instant::now(),
         })
     }
 
-    pub async fn sync_historical(&self) -> Result<()> {
+    /// Stop starting new work; in-flight batches get `SHUTDOWN_TIMEOUT_SECS` to finish
+    pub fn shutdown(&self) {
+        self.shutdown.trigger();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_requested()
+    }
+
+    /// Run `work` to completion, or give up on it once shutdown has been requested for longer
+    /// than the shutdown timeout. Abandoned work is not checkpointed and is redone on restart.
+    async fn drain<T>(&self, work: impl Future<Output = T>) -> Option<T> {
+        tokio::pin!(work);
+        tokio::select! {
+            output = &mut work => return Some(output),
+            _ = self.shutdown.wait() => {}
+        }
+
+        let timeout = Duration::from_secs(self.config.shutdown_timeout_secs);
+        info!("Waiting up to {:?} for in-flight work to finish", timeout);
+        tokio::time::timeout(timeout, work).await.ok()
+    }
+
+    /// Log what this run indexed before the process exits
+    pub fn log_summary(&self) {
+        let uptime = self.started_at.elapsed().as_secs();
+        info!("");
+        info!("========== SHUTDOWN ==========");
+        info!(
+            "Checkpoint: block {}",
+            self.checkpoint.load(Ordering::Relaxed)
+        );
+        info!("Blocks indexed this run: {}", METRICS.blocks_indexed.get());
+        info!(
+            "Transactions indexed this run: {}",
+            METRICS.transactions_indexed.get()
+        );
+        info!("Uptime: {}m {}s", uptime / 60, uptime % 60);
+        info!("==============================");
+    }
+
+    /// Checkpoint, chain head and time of the last write, as tracked by the running sync
+    pub fn sync_state(&self) -> SyncState {
+        let last_indexed_at = self.last_indexed_at.load(Ordering::Relaxed);
+        SyncState {
+            checkpoint: self.checkpoint.load(Ordering::Relaxed),
+            head_block: self.rpc.best_head(),
+            last_indexed_at: (last_indexed_at > 0).then_some(last_indexed_at),
+        }
+    }
+
+    /// Check that the RPC pool and the sink are reachable and the sync keeps up
+    pub async fn readiness(&self) -> Readiness {
+        let sink_reachable = tokio::time::timeout(READY_CHECK_TIMEOUT, self.sink.ping())
+            .await
+            .unwrap_or(false);
+
+        Readiness::new(
+            self.sync_state(),
+            self.rpc.is_available(),
+            sink_reachable,
+            self.config.ready_max_lag,
+        )
+    }
+
+    fn publish_checkpoint(&self, progress: &SyncProgress) {
+        let checkpoint = progress.checkpoint();
+        self.checkpoint.store(checkpoint, Ordering::Relaxed);
+        METRICS.set_indexed_block(checkpoint);
+    }
+
+    fn record_indexed(&self, blocks: &[IndexedBlock]) {
+        METRICS.record_indexed(blocks);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        self.last_indexed_at.store(now, Ordering::Relaxed);
+    }
+
+    /// Backfill from the checkpoint up to the indexable head, or up to `to` when it is lower
+    pub async fn sync_historical(&self, to: Option<u64>) -> Result<()> {
         info!("");
         info!("========== HISTORICAL SYNC ==========");
 
-        // Get checkpoint from Elasticsearch
-        let last_indexed = self.es_client.get_last_indexed_block().await?;
-        info!("Last indexed block in Elasticsearch: {}", last_indexed);
+        // Get checkpoint and pending failures from the sink
+        let mut progress = self.load_progress().await?;
+        info!("Last indexed block in the sink: {}", progress.checkpoint());
         info!("Configured start block: {}", self.config.start_block);
 
-        let start_block = last_indexed.max(self.config.start_block);
-        let current_block = self.provider.get_block_number().await?.as_u64();
+        self.retry_failed_blocks(&mut progress).await?;
 
-        info!("Current block on chain: {}", current_block);
+        let start_block = progress.scanned_through.max(self.config.start_block);
+        let head = self.indexing_head().await?;
+        let current_block = to.map_or(head, |to| to.min(head));
+
+        info!("Current indexable block on chain: {}", head);
         info!("Will start indexing from block: {}", start_block);
+        if current_block < head {
+            info!("Will stop indexing at block: {}", current_block);
+        }
 
         if start_block >= current_block {
             info!("Already synced up to current block!");
@@ -81,6 +231,14 @@ impl BlockIndexer {
         let start_time = SystemTime::now();
 
         for batch_start in (start_block..=current_block).step_by(batch_size) {
+            if self.shutdown.is_requested() {
+                info!(
+                    "Shutdown requested, not starting batch at block {}",
+                    batch_start
+                );
+                break;
+            }
+
             let batch_end = (batch_start + batch_size as u64 - 1).min(current_block);
             let batch_size_actual = batch_end - batch_start + 1;
 
@@ -89,54 +247,79 @@ impl BlockIndexer {
                 batch_start, batch_end, batch_size_actual
             );
 
-            match self.index_block_range(batch_start, batch_end).await {
-                Ok(_) => {
-                    processed += batch_size_actual as usize;
-
-                    // Calculate progress
-                    let processed_u64 = processed as u64;
-                    let progress_pct = (processed_u64 as f64 / total_to_process as f64) * 100.0;
-                    let elapsed = start_time.elapsed().unwrap().as_secs();
-                    let blocks_per_sec = if elapsed > 0 {
-                        processed_u64 as f64 / elapsed as f64
-                    } else {
-                        0.0
-                    };
-                    let remaining = total_to_process.saturating_sub(processed_u64);
-                    let eta_secs = if blocks_per_sec > 0.0 && remaining > 0 {
-                        (remaining as f64 / blocks_per_sec) as u64
-                    } else {
-                        0
-                    };
+            let Some(failed) = self
+                .drain(self.index_block_range(batch_start, batch_end))
+                .await
+            else {
+                warn!(
+                    "Batch {}-{} did not finish before the shutdown timeout, it will be re-indexed on restart",
+                    batch_start, batch_end
+                );
+                break;
+            };
+            processed += batch_size_actual as usize - failed.len();
 
-                    info!("Batch completed: blocks {}-{}", batch_start, batch_end);
-                    info!(
-                        "  Progress: {}/{} blocks ({:.2}%)",
-                        processed, total_to_process, progress_pct
-                    );
-                    info!("  Speed: {:.2} blocks/sec", blocks_per_sec);
-                    if eta_secs > 0 {
-                        let eta_mins = eta_secs / 60;
-                        let eta_secs_remain = eta_secs % 60;
-                        info!("  ETA: {}m {}s", eta_mins, eta_secs_remain);
-                    }
+            // Calculate progress
+            let processed_u64 = processed as u64;
+            let progress_pct = (processed_u64 as f64 / total_to_process as f64) * 100.0;
+            let elapsed = start_time.elapsed().unwrap().as_secs();
+            let blocks_per_sec = if elapsed > 0 {
+                processed_u64 as f64 / elapsed as f64
+            } else {
+                0.0
+            };
+            let remaining = total_to_process.saturating_sub(processed_u64);
+            let eta_secs = if blocks_per_sec > 0.0 && remaining > 0 {
+                (remaining as f64 / blocks_per_sec) as u64
+            } else {
+                0
+            };
 
-                    // Set checkpoint after each batch
-                    self.es_client.set_checkpoint(batch_end).await?;
-                    debug!("Checkpoint saved: block {}", batch_end);
-                }
-                Err(e) => {
-                    error!("Error syncing blocks {}-{}: {}", batch_start, batch_end, e);
-                    // Continue with next batch
-                }
+            if failed.is_empty() {
+                info!("Batch completed: blocks {}-{}", batch_start, batch_end);
+            } else {
+                error!(
+                    "Batch completed with {} failed block(s): blocks {}-{}",
+                    failed.len(),
+                    batch_start,
+                    batch_end
+                );
+            }
+            info!(
+                "  Progress: {}/{} blocks ({:.2}%)",
+                processed, total_to_process, progress_pct
+            );
+            info!("  Speed: {:.2} blocks/sec", blocks_per_sec);
+            info!("  RPC concurrency limit: {}", self.concurrency.limit());
+            if eta_secs > 0 {
+                let eta_mins = eta_secs / 60;
+                let eta_secs_remain = eta_secs % 60;
+                info!("  ETA: {}m {}s", eta_mins, eta_secs_remain);
             }
 
+            // Set checkpoint after each batch; it holds at the lowest failed block
+            self.record_progress(&mut progress, batch_end, &failed);
+            self.sink.set_progress(&progress).await?;
+            debug!("Checkpoint saved: block {}", progress.checkpoint());
+
             info!("");
 
             // Small delay to avoid overwhelming the RPC (reduced from 100ms)
             sleep(Duration::from_millis(10)).await;
         }
 
+        if self.shutdown.is_requested() {
+            self.sink.set_progress(&progress).await?;
+            info!(
+                "Historical sync stopped, checkpoint saved at block {}",
+                progress.checkpoint()
+            );
+            return Ok(());
+        }
+
+        // Give blocks that failed during the backfill one more chance
+        self.retry_failed_blocks(&mut progress).await?;
+
         let total_time = start_time.elapsed().unwrap().as_secs();
         let total_mins = total_time / 60;
         let total_secs = total_time % 60;
@@ -150,35 +333,620 @@ impl BlockIndexer {
                 processed as f64 / total_time as f64
             );
         }
+        if !progress.failed_blocks.is_empty() {
+            warn!(
+                "{} block(s) still failing, checkpoint held at block {}: {:?}",
+                progress.failed_blocks.len(),
+                progress.checkpoint(),
+                progress.failed_blocks
+            );
+        }
         info!("=====================================");
         info!("");
 
         Ok(())
     }
 
+    /// Load the sync progress and drop any blocks the operator asked to skip
+    async fn load_progress(&self) -> Result<SyncProgress> {
+        let mut progress = self.sink.get_progress().await?;
+
+        let skipped = progress.skip(&self.config.skip_blocks);
+        if !skipped.is_empty() {
+            warn!("Skipping failed block(s) as configured: {:?}", skipped);
+            self.sink.set_progress(&progress).await?;
+        }
+
+        self.publish_checkpoint(&progress);
+        Ok(progress)
+    }
+
+    /// Record a processed range, ignoring failures of blocks configured to be skipped
+    fn record_progress(&self, progress: &mut SyncProgress, to: u64, failed: &[u64]) {
+        let failed: Vec<u64> = failed
+            .iter()
+            .copied()
+            .filter(|b| !self.config.skip_blocks.contains(b))
+            .collect();
+        progress.record(to, &failed);
+        self.publish_checkpoint(progress);
+    }
+
+    /// Re-index blocks that failed earlier so the checkpoint can move past them
+    async fn retry_failed_blocks(&self, progress: &mut SyncProgress) -> Result<()> {
+        if progress.failed_blocks.is_empty() {
+            return Ok(());
+        }
+
+        let pending: Vec<u64> = progress.failed_blocks.iter().copied().collect();
+        info!("Retrying {} previously failed block(s)", pending.len());
+
+        let still_failed = self.index_blocks(&pending).await;
+        let resolved: Vec<u64> = pending
+            .into_iter()
+            .filter(|b| !still_failed.contains(b))
+            .collect();
+
+        for block_number in &resolved {
+            progress.resolve(*block_number);
+        }
+        self.publish_checkpoint(progress);
+
+        // Their neighbours were indexed without them, so a reorg in between went unnoticed
+        if let Some(orphaned) = self.find_orphaned_neighbour(&resolved).await? {
+            warn!(
+                "Reorg detected around re-indexed blocks: stored block {} is not an ancestor",
+                orphaned
+            );
+            self.handle_reorg(orphaned, progress).await?;
+        }
+
+        if !resolved.is_empty() {
+            info!(
+                "Re-indexed {} failed block(s), checkpoint now at block {}",
+                resolved.len(),
+                progress.checkpoint()
+            );
+            self.sink.set_progress(progress).await?;
+        }
+        if !still_failed.is_empty() {
+            warn!(
+                "{} block(s) still failing: {:?}",
+                still_failed.len(),
+                still_failed
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Check freshly indexed blocks against their stored neighbours. Returns the lowest stored
+    /// neighbour that does not link up with them, which a reorg must have orphaned.
+    async fn find_orphaned_neighbour(&self, block_numbers: &[u64]) -> Result<Option<u64>> {
+        let mut neighbourhood: Vec<u64> = block_numbers
+            .iter()
+            .flat_map(|&n| [n.saturating_sub(1), n, n + 1])
+            .collect();
+        neighbourhood.sort_unstable();
+        neighbourhood.dedup();
+        let stored = self.sink.get_blocks(&neighbourhood).await?;
+
+        let mut orphaned = Vec::new();
+        for n in block_numbers {
+            let Some(block) = stored.get(n) else { continue };
+            let previous = n.checked_sub(1).and_then(|p| stored.get(&p));
+            if previous.is_some_and(|previous| previous.hash != block.parent_hash) {
+                orphaned.push(n - 1);
+            }
+            if let Some(next) = stored.get(&(n + 1)) {
+                if next.parent_hash != block.hash {
+                    orphaned.push(n + 1);
+                }
+            }
+        }
+        Ok(orphaned.into_iter().min())
+    }
+
+    /// Fetch and overwrite the stored blocks in `from..=to`, whatever the checkpoint says
+    pub async fn reindex(&self, from: u64, to: u64) -> Result<()> {
+        if from > to {
+            anyhow::bail!("--from ({}) must not be above --to ({})", from, to);
+        }
+
+        info!("");
+        info!("========== REINDEX ==========");
+
+        let mut progress = self.load_progress().await?;
+        let mut reindexed = 0u64;
+        let mut failed = Vec::new();
+        for batch_start in (from..=to).step_by(self.config.batch_size) {
+            let batch_end = (batch_start + self.config.batch_size as u64 - 1).min(to);
+            info!("Re-indexing blocks {} to {}", batch_start, batch_end);
+
+            let batch_failed = self.index_block_range(batch_start, batch_end).await;
+            reindexed += batch_end - batch_start + 1 - batch_failed.len() as u64;
+            failed.extend(batch_failed);
+        }
+
+        // Failures within the scanned range hold the checkpoint; later blocks are left to the sync
+        let scanned_through = progress.scanned_through;
+        let held: Vec<u64> = failed
+            .iter()
+            .copied()
+            .filter(|b| *b <= scanned_through)
+            .collect();
+        if !held.is_empty() {
+            self.record_progress(&mut progress, scanned_through, &held);
+            self.sink.set_progress(&progress).await?;
+        }
+
+        if let Err(e) = self.sink.refresh().await {
+            warn!("Failed to refresh blocks index after reindex: {}", e);
+        }
+
+        info!("Reindex summary:");
+        info!("  Blocks re-indexed: {}", reindexed);
+        info!("  Blocks failed: {}", failed.len());
+        if !held.is_empty() {
+            warn!(
+                "Checkpoint held at block {} until {:?} are indexed",
+                progress.checkpoint(),
+                held
+            );
+        }
+        info!("=============================");
+
+        Ok(())
+    }
+
+    /// Index `from..=to`, or up to the indexable head without `to`, leaving the checkpoint
+    /// where it is. The sync only moves the checkpoint over blocks it indexed itself, so a
+    /// range on either side of it is filled like a reindex.
+    pub async fn backfill_range(&self, from: u64, to: Option<u64>) -> Result<()> {
+        let to = match to {
+            Some(to) => to,
+            None => self.indexing_head().await?,
+        };
+        self.reindex(from, to).await
+    }
+
+    /// Print the checkpoint, the chain head and the lag between them
+    pub async fn status(&self) -> Result<()> {
+        let progress = self.sink.get_progress().await?;
+        let head = self
+            .rpc
+            .best_head()
+            .context("No RPC endpoint reported its head")?;
+        let indexable = self.indexing_head().await?;
+        let checkpoint = progress.checkpoint();
+
+        info!("");
+        info!("========== STATUS ==========");
+        info!("Checkpoint: block {}", checkpoint);
+        info!("Scanned through: block {}", progress.scanned_through);
+        if !progress.failed_blocks.is_empty() {
+            info!(
+                "Failed blocks: {} {:?}",
+                progress.failed_blocks.len(),
+                progress.failed_blocks
+            );
+        }
+        info!("Chain head: block {}", head);
+        info!(
+            "Indexable head ({:?}): block {}",
+            self.config.finality_mode, indexable
+        );
+        info!("Lag: {} block(s)", head.saturating_sub(checkpoint));
+        info!("============================");
+
+        Ok(())
+    }
+
+    /// Move the checkpoint to `to`. Moving it forward treats the blocks in between as indexed;
+    /// moving it back makes the next sync index them again.
+    pub async fn reset_checkpoint(&self, to: u64) -> Result<()> {
+        let mut progress = self.sink.get_progress().await?;
+        let previous = progress.checkpoint();
+
+        progress.reset(to);
+        self.sink.set_progress(&progress).await?;
+
+        info!(
+            "Checkpoint moved from block {} to block {}",
+            previous,
+            progress.checkpoint()
+        );
+        if to > previous {
+            warn!(
+                "Blocks {} to {} are now treated as indexed; run `repair` to backfill any that are missing",
+                previous + 1,
+                to
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Find blocks missing from the index between `START_BLOCK` and the checkpoint and
+    /// backfill exactly those ranges. With `dry_run` only the report is printed.
+    pub async fn repair_gaps(&self, dry_run: bool) -> Result<()> {
+        info!("");
+        info!("========== GAP REPAIR ==========");
+
+        let mut progress = self.load_progress().await?;
+        let from = self.config.start_block;
+        let to = progress.checkpoint();
+
+        if to < from {
+            info!(
+                "Nothing indexed yet between block {} and the checkpoint",
+                from
+            );
+            return Ok(());
+        }
+
+        info!("Scanning blocks {} to {} for gaps...", from, to);
+        let gaps = self.find_gaps(from, to).await?;
+        let missing = gaps::count_blocks(&gaps);
+
+        info!(
+            "Found {} missing block(s) in {} range(s)",
+            missing,
+            gaps.len()
+        );
+        for (start, end) in &gaps {
+            if start == end {
+                info!("  missing: {}", start);
+            } else {
+                info!("  missing: {}-{} ({} blocks)", start, end, end - start + 1);
+            }
+        }
+
+        if dry_run || gaps.is_empty() {
+            if dry_run {
+                info!("Dry run: no blocks were indexed");
+            }
+            info!("================================");
+            return Ok(());
+        }
+
+        let mut repaired = 0u64;
+        let mut failed = Vec::new();
+        for (start, end) in gaps {
+            for batch_start in (start..=end).step_by(self.config.batch_size) {
+                let batch_end = (batch_start + self.config.batch_size as u64 - 1).min(end);
+                info!("Backfilling blocks {} to {}", batch_start, batch_end);
+
+                let batch_failed = self.index_block_range(batch_start, batch_end).await;
+                repaired += batch_end - batch_start + 1 - batch_failed.len() as u64;
+                failed.extend(batch_failed);
+            }
+        }
+
+        // Blocks that still fail pull the checkpoint back below them
+        if !failed.is_empty() {
+            let scanned_through = progress.scanned_through;
+            self.record_progress(&mut progress, scanned_through, &failed);
+            self.sink.set_progress(&progress).await?;
+        }
+
+        if let Err(e) = self.sink.refresh().await {
+            warn!("Failed to refresh blocks index after repair: {}", e);
+        }
+
+        info!("Repair summary:");
+        info!("  Missing blocks found: {}", missing);
+        info!("  Blocks repaired: {}", repaired);
+        info!("  Blocks still failing: {}", failed.len());
+        if !failed.is_empty() {
+            warn!(
+                "Checkpoint held at block {} until {:?} are indexed",
+                progress.checkpoint(),
+                failed
+            );
+        }
+        info!("================================");
+
+        Ok(())
+    }
+
+    /// Missing block ranges in `from..=to`, narrowing down with histogram aggregations
+    async fn find_gaps(&self, from: u64, to: u64) -> Result<Vec<(u64, u64)>> {
+        let mut pending = vec![(from, to)];
+        let mut missing = Vec::new();
+
+        while let Some((lo, hi)) = pending.pop() {
+            let interval = gaps::histogram_interval(lo, hi);
+            let buckets = self.sink.block_count_histogram(lo, hi, interval).await?;
+
+            for (key, count) in buckets {
+                let (start, end) = gaps::bucket_bounds(key, interval, lo, hi);
+                if start > end {
+                    continue;
+                }
+
+                let expected = end - start + 1;
+                if count == 0 {
+                    missing.push((start, end));
+                } else if count < expected {
+                    pending.push((start, end));
+                }
+            }
+        }
+
+        Ok(gaps::merge_ranges(missing))
+    }
+
+    /// Compare stored blocks with the chain and report, and optionally re-index, mismatches
+    pub async fn verify(&self, options: VerifyOptions) -> Result<()> {
+        info!("");
+        info!("========== VERIFY ==========");
+
+        let mut progress = self.load_progress().await?;
+        let from = options.from.unwrap_or(self.config.start_block);
+        let to = options.to.unwrap_or_else(|| progress.checkpoint());
+
+        if to < from {
+            info!("Nothing to verify between block {} and block {}", from, to);
+            return Ok(());
+        }
+
+        let block_numbers = match options.sample {
+            Some(count) => {
+                let seed = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_nanos() as u64;
+                verify::sample_blocks(from, to, count, seed)
+            }
+            None => (from..=to).collect(),
+        };
+
+        info!(
+            "Verifying {} block(s) between {} and {}{}",
+            block_numbers.len(),
+            from,
+            to,
+            if options.sample.is_some() {
+                " (sampled)"
+            } else {
+                ""
+            }
+        );
+
+        let mut mismatched = Vec::new();
+        let mut checked = 0;
+
+        let sinks = self.verified_sinks();
+        for chunk in block_numbers.chunks(self.config.batch_size) {
+            let stored =
+                futures::future::try_join_all(sinks.iter().map(|sink| sink.get_blocks(chunk)))
+                    .await?;
+
+            let chain_blocks: Vec<(u64, Result<ChainBlock>)> = stream::iter(chunk.iter().copied())
+                .map(|block_num| async move {
+                    let _permit = self.concurrency.acquire().await;
+                    (block_num, self.fetch_chain_block(block_num).await)
+                })
+                .buffer_unordered(self.config.max_concurrency)
+                .collect()
+                .await;
+
+            for (block_num, chain_block) in chain_blocks {
+                let chain_block = match chain_block {
+                    Ok(chain_block) => chain_block,
+                    Err(e) => {
+                        warn!("Could not fetch block {} from RPC: {}", block_num, e);
+                        continue;
+                    }
+                };
+
+                checked += 1;
+                let mut matches = true;
+                for (sink, stored) in sinks.iter().zip(&stored) {
+                    let mismatches = verify::compare_block(stored.get(&block_num), &chain_block);
+                    if !mismatches.is_empty() {
+                        warn!(
+                            "Block {} mismatch in {}: {}",
+                            block_num,
+                            sink.name(),
+                            mismatches.join(", ")
+                        );
+                        matches = false;
+                    }
+                }
+                if !matches {
+                    mismatched.push(block_num);
+                }
+            }
+        }
+
+        mismatched.sort_unstable();
+
+        info!("Verification summary:");
+        info!("  Blocks checked: {}", checked);
+        info!("  Mismatched blocks: {}", mismatched.len());
+
+        if options.fix && !mismatched.is_empty() {
+            info!("Re-indexing {} mismatched block(s)...", mismatched.len());
+            // Transactions and logs that left the canonical blocks would otherwise remain
+            self.sink.delete_block_children(&mismatched).await?;
+            let failed = self.index_blocks(&mismatched).await;
+
+            if !failed.is_empty() {
+                let scanned_through = progress.scanned_through;
+                self.record_progress(&mut progress, scanned_through, &failed);
+                self.sink.set_progress(&progress).await?;
+            }
+
+            if let Err(e) = self.sink.refresh().await {
+                warn!("Failed to refresh blocks index after verify: {}", e);
+            }
+
+            info!("  Blocks re-indexed: {}", mismatched.len() - failed.len());
+            info!("  Blocks still failing: {}", failed.len());
+        }
+        info!("============================");
+
+        Ok(())
+    }
+
+    /// The sinks verify compares with the chain: every member of a fan-out that keeps history
+    fn verified_sinks(&self) -> Vec<&dyn Sink> {
+        match self.sink.members() {
+            Some(members) => members
+                .iter()
+                .map(Box::as_ref)
+                .filter(|sink| sink.keeps_history())
+                .collect(),
+            None => vec![self.sink.as_ref()],
+        }
+    }
+
+    async fn fetch_chain_block(&self, block_number: u64) -> Result<ChainBlock> {
+        let block = self
+            .rpc
+            .call(|endpoint| async move {
+                endpoint
+                    .provider
+                    .get_block(block_number)
+                    .await
+                    .map_err(IndexerError::from)
+                    .context("Failed to fetch block from RPC")?
+                    .ok_or(IndexerError::BlockNotFound(block_number).into())
+            })
+            .await?;
+
+        Ok(ChainBlock {
+            hash: format!("{:?}", block.hash.context("Block has no hash")?),
+            parent_hash: format!("{:?}", block.parent_hash),
+            transaction_hashes: block
+                .transactions
+                .iter()
+                .map(|h| format!("{:?}", h))
+                .collect(),
+        })
+    }
+
     pub async fn sync_live(&self) -> Result<()> {
         info!("");
         info!("========== LIVE SYNC MODE ==========");
-        info!("Sync interval: {} seconds", self.config.sync_interval_secs);
+        match &self.config.rpc_ws_url {
+            Some(ws_url) => info!("Following newHeads on {}", ws_url),
+            None => info!("Sync interval: {} seconds", self.config.sync_interval_secs),
+        }
         info!("Monitoring for new blocks...");
         info!("====================================");
         info!("");
 
-        loop {
-            match self.sync_new_blocks().await {
-                Ok(_) => {}
-                Err(e) => {
-                    warn!("Error in live sync: {}", e);
+        // Progress is kept in memory between iterations instead of re-read from the sink
+        let mut progress = self.load_progress().await?;
+
+        while !self.shutdown.is_requested() {
+            match &self.config.rpc_ws_url {
+                Some(ws_url) => {
+                    let result = self.follow_new_heads(ws_url, &mut progress).await;
+                    if self.shutdown.is_requested() {
+                        break;
+                    }
+                    match result {
+                        Ok(_) => warn!("newHeads subscription ended, falling back to HTTP polling"),
+                        Err(e) => warn!(
+                            "WebSocket live sync failed: {}, falling back to HTTP polling",
+                            e
+                        ),
+                    }
+
+                    // Keep indexing over HTTP until it is time to reconnect
+                    let reconnect_at = tokio::time::Instant::now() + WS_RECONNECT_DELAY;
+                    while tokio::time::Instant::now() < reconnect_at
+                        && !self.shutdown.is_requested()
+                    {
+                        self.poll_new_blocks(&mut progress).await;
+                    }
+                    if !self.shutdown.is_requested() {
+                        info!("Reconnecting to {}", ws_url);
+                    }
                 }
+                None => self.poll_new_blocks(&mut progress).await,
             }
+        }
+
+        self.sink.set_progress(&progress).await?;
+        info!(
+            "Live sync stopped, checkpoint saved at block {}",
+            progress.checkpoint()
+        );
+
+        Ok(())
+    }
 
-            sleep(Duration::from_secs(self.config.sync_interval_secs)).await;
+    /// One live sync iteration; once shutdown is requested it gets the shutdown timeout to finish
+    async fn sync_new_blocks_draining(&self, progress: &mut SyncProgress, latest: Option<u64>) {
+        match self.drain(self.sync_new_blocks(progress, latest)).await {
+            Some(Ok(())) => {}
+            Some(Err(e)) => warn!("Error in live sync: {}", e),
+            None => warn!("Live sync iteration did not finish before the shutdown timeout"),
         }
     }
 
-    async fn sync_new_blocks(&self) -> Result<()> {
-        let last_indexed = self.es_client.get_last_indexed_block().await?;
-        let current_block = self.provider.get_block_number().await?.as_u64();
+    /// One HTTP polling iteration of live sync
+    async fn poll_new_blocks(&self, progress: &mut SyncProgress) {
+        self.sync_new_blocks_draining(progress, None).await;
+
+        tokio::select! {
+            _ = sleep(Duration::from_secs(self.config.sync_interval_secs)) => {}
+            _ = self.shutdown.wait() => {}
+        }
+    }
+
+    /// Drive live sync from an `eth_subscribe("newHeads")` subscription until it drops
+    async fn follow_new_heads(&self, ws_url: &str, progress: &mut SyncProgress) -> Result<()> {
+        let ws = Provider::<Ws>::connect(ws_url)
+            .await
+            .context("Failed to connect to RPC WebSocket")?;
+        let mut heads = ws
+            .subscribe_blocks()
+            .await
+            .context("Failed to subscribe to newHeads")?;
+
+        info!("Subscribed to newHeads on {}", ws_url);
+
+        // Backfill any heads missed while disconnected
+        self.sync_new_blocks_draining(progress, None).await;
+
+        while !self.shutdown.is_requested() {
+            let next = tokio::select! {
+                next = tokio::time::timeout(WS_HEAD_TIMEOUT, heads.next()) => next,
+                _ = self.shutdown.wait() => break,
+            };
+            let head = match next {
+                Ok(Some(head)) => head,
+                Ok(None) => return Ok(()),
+                Err(_) => anyhow::bail!("no new head received for {:?}", WS_HEAD_TIMEOUT),
+            };
+
+            let head_number = head.number.map(|n| n.as_u64());
+            debug!("New head: {:?}", head_number);
+
+            self.sync_new_blocks_draining(progress, head_number).await;
+        }
+
+        Ok(())
+    }
+
+    /// Index everything between the last scanned block and the head. `latest` is the chain
+    /// head when it is already known, e.g. from a newHeads notification.
+    async fn sync_new_blocks(
+        &self,
+        progress: &mut SyncProgress,
+        latest: Option<u64>,
+    ) -> Result<()> {
+        self.retry_failed_blocks(progress).await?;
+
+        let last_indexed = progress.scanned_through;
+        let current_block = self.indexing_head_from(latest).await?;
+        let (safe, finalized) = self.finality_heads().await;
 
         if current_block > last_indexed {
             let new_blocks = current_block - last_indexed;
@@ -189,34 +957,106 @@ impl BlockIndexer {
                 current_block
             );
 
+            // Hash of the previously indexed block, used to detect reorgs. It is loaded from
+            // the sink when unknown, at the start and after a block failed.
+            let mut parent_hash: Option<String> = None;
+
             let mut indexed_count = 0;
             for block_num in (last_indexed + 1)..=current_block {
-                match self.index_block(block_num).await {
+                if self.shutdown.is_requested() {
+                    break;
+                }
+
+                let mut block = match self.fetch_block(block_num).await {
+                    Ok(block) => block,
+                    Err(e) => {
+                        error!(
+                            "Error fetching block {} ({}): {:#}",
+                            block_num,
+                            rpc::error_kind(&e),
+                            e
+                        );
+                        METRICS
+                            .block_failures
+                            .with_label_values(&[rpc::error_kind(&e)])
+                            .inc();
+                        parent_hash = None;
+                        // Hold the checkpoint and retry this block on the next iteration
+                        self.record_progress(progress, block_num, &[block_num]);
+                        self.sink.set_progress(progress).await?;
+                        continue;
+                    }
+                };
+
+                let expected = match parent_hash.take() {
+                    Some(hash) => Some(hash),
+                    None => self.sink.get_block(block_num - 1).await?.map(|b| b.hash),
+                };
+                if let Some(expected) = &expected {
+                    if block.parent_hash != *expected {
+                        warn!(
+                            "Reorg detected at block {}: parent hash {} does not match stored hash {}",
+                            block_num, block.parent_hash, expected
+                        );
+                        self.handle_reorg(block_num - 1, progress).await?;
+                        return Ok(());
+                    }
+                }
+
+                block.finality = FinalityStatus::for_block(block_num, safe, finalized);
+
+                // The block and the checkpoint covering it are committed together
+                let mut written = progress.clone();
+                written.record(block_num, &[]);
+
+                match self
+                    .sink
+                    .write_blocks_with_progress(std::slice::from_ref(&block), &written)
+                    .await
+                {
                     Ok(_) => {
+                        self.record_indexed(std::slice::from_ref(&block));
                         indexed_count += 1;
-                        self.es_client.set_checkpoint(block_num).await?;
-                        debug!("Indexed block {} and saved checkpoint", block_num);
-                        
+                        parent_hash = Some(block.hash);
+                        *progress = written;
+                        self.publish_checkpoint(progress);
+                        debug!(
+                            "Indexed block {} and saved checkpoint (block {})",
+                            block_num,
+                            progress.checkpoint()
+                        );
+
                         // Refresh every 10 blocks or at the end to make blocks visible immediately
                         if indexed_count % 10 == 0 || block_num == current_block {
-                            if let Err(e) = self.es_client.refresh_blocks_index().await {
+                            if let Err(e) = self.sink.refresh().await {
                                 warn!("Failed to refresh blocks index: {}", e);
                             }
                         }
                     }
                     Err(e) => {
                         error!("Error indexing block {}: {}", block_num, e);
-                        // Continue with next block
+                        METRICS
+                            .block_failures
+                            .with_label_values(&[rpc::error_kind(&e)])
+                            .inc();
+                        parent_hash = None;
+                        // Hold the checkpoint and retry this block on the next iteration
+                        self.record_progress(progress, block_num, &[block_num]);
+                        self.sink.set_progress(progress).await?;
                     }
                 }
             }
 
             // Final refresh to ensure all blocks are visible
-            if let Err(e) = self.es_client.refresh_blocks_index().await {
+            if let Err(e) = self.sink.refresh().await {
                 warn!("Failed to refresh blocks index after sync: {}", e);
             }
 
-            info!("Live sync completed: now at block {}", current_block);
+            info!(
+                "Live sync completed: now at block {} (checkpoint {})",
+                current_block,
+                progress.checkpoint()
+            );
         } else {
             debug!(
                 "No new blocks (last indexed: {}, current: {})",
@@ -224,43 +1064,210 @@ impl BlockIndexer {
             );
         }
 
+        self.promote_finality(safe, finalized).await;
+
         Ok(())
     }
 
-    async fn index_block_range(&self, from: u64, to: u64) -> Result<()> {
-        let semaphore = Arc::new(Semaphore::new(self.config.concurrency));
-        let mut indexed_blocks = Vec::new();
-        let mut error_count = 0;
+    /// Highest block that may be indexed under the configured finality mode
+    async fn indexing_head(&self) -> Result<u64> {
+        self.indexing_head_from(None).await
+    }
 
-        // Process blocks in parallel with concurrency limit
-        let block_numbers: Vec<u64> = (from..=to).collect();
-        let total_blocks = block_numbers.len();
+    /// Like `indexing_head`, reusing `latest` as the chain head when it is known
+    async fn indexing_head_from(&self, latest: Option<u64>) -> Result<u64> {
+        let latest = async {
+            let latest = match latest {
+                Some(latest) => latest,
+                None => self
+                    .rpc
+                    .call(|endpoint| async move {
+                        Ok(endpoint
+                            .provider
+                            .get_block_number()
+                            .await
+                            .map_err(IndexerError::from)?)
+                    })
+                    .await?
+                    .as_u64(),
+            };
+            METRICS.set_head_block(latest);
+            Ok::<u64, anyhow::Error>(latest)
+        };
 
-        let results: Vec<(u64, Result<IndexedBlock>)> = stream::iter(block_numbers.iter().cloned())
-            .map(|block_num| {
-                let provider = Arc::clone(&self.provider);
-                let semaphore = Arc::clone(&semaphore);
+        match self.config.finality_mode {
+            FinalityMode::Latest => latest.await,
+            FinalityMode::Confirmations(confirmations) => {
+                Ok(latest.await?.saturating_sub(confirmations))
+            }
+            FinalityMode::Safe => self.tagged_block_number(BlockNumber::Safe).await,
+            FinalityMode::Finalized => self.tagged_block_number(BlockNumber::Finalized).await,
+        }
+    }
 
-                async move {
-                    let _permit = semaphore.acquire().await.unwrap();
-                    let result = Self::index_block_internal(block_num, &provider).await;
-                    (block_num, result)
-                }
+    async fn tagged_block_number(&self, tag: BlockNumber) -> Result<u64> {
+        let block = self
+            .rpc
+            .call(|endpoint| async move {
+                endpoint
+                    .provider
+                    .get_block(tag)
+                    .await
+                    .map_err(IndexerError::from)
+                    .with_context(|| format!("Failed to fetch {} block from RPC", tag))?
+                    .with_context(|| format!("Node returned no {} block", tag))
             })
-            .buffer_unordered(self.config.concurrency)
-            .collect()
-            .await;
+            .await?;
+
+        Ok(block.number.context("Tagged block has no number")?.as_u64())
+    }
+
+    /// Current `safe` and `finalized` heads, or `None` for tags the node does not support
+    async fn finality_heads(&self) -> (Option<u64>, Option<u64>) {
+        let safe = self.tagged_block_number(BlockNumber::Safe).await.ok();
+        let finalized = self.tagged_block_number(BlockNumber::Finalized).await.ok();
+        (safe, finalized)
+    }
+
+    /// Update the finality status of already indexed blocks once the node's heads advance
+    async fn promote_finality(&self, safe: Option<u64>, finalized: Option<u64>) {
+        let targets = [
+            (safe, FinalityStatus::Safe, &self.safe_marked),
+            (finalized, FinalityStatus::Finalized, &self.finalized_marked),
+        ];
+
+        for (head, status, marked) in targets {
+            let Some(head) = head else { continue };
+            if head <= marked.load(Ordering::Relaxed) {
+                continue;
+            }
+
+            match self.sink.update_finality(head, status).await {
+                Ok(updated) => {
+                    if updated > 0 {
+                        debug!(
+                            "Marked {} block(s) as {} up to block {}",
+                            updated,
+                            status.as_str(),
+                            head
+                        );
+                    }
+                    marked.store(head, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    warn!("Failed to update finality up to block {}: {}", head, e);
+                }
+            }
+        }
+    }
+
+    /// Walk back from `tip` until the stored hash matches the canonical chain, then drop
+    /// the orphaned blocks and rewind the checkpoint to the common ancestor. The canonical
+    /// blocks are re-indexed by the next live sync iteration.
+    async fn handle_reorg(&self, tip: u64, progress: &mut SyncProgress) -> Result<()> {
+        let max_depth = self.config.max_reorg_depth;
+        let mut height = tip;
+
+        let ancestor = loop {
+            if tip - height > max_depth {
+                anyhow::bail!(
+                    "Reorg below block {} is deeper than MAX_REORG_DEPTH ({})",
+                    tip,
+                    max_depth
+                );
+            }
+
+            let Some(stored) = self.sink.get_block(height).await? else {
+                warn!(
+                    "Block {} is not indexed, treating it as the common ancestor",
+                    height
+                );
+                break height;
+            };
+
+            let canonical = self
+                .rpc
+                .call(|endpoint| async move {
+                    endpoint
+                        .provider
+                        .get_block(height)
+                        .await
+                        .map_err(IndexerError::from)
+                        .context("Failed to fetch block from RPC")?
+                        .ok_or(IndexerError::BlockNotFound(height).into())
+                })
+                .await?;
+            let canonical_hash = format!("{:?}", canonical.hash.context("Block has no hash")?);
+
+            if stored.hash == canonical_hash {
+                break height;
+            }
+
+            debug!(
+                "Block {} is orphaned (stored {}, canonical {})",
+                height, stored.hash, canonical_hash
+            );
+
+            if height == 0 {
+                anyhow::bail!("Stored genesis block does not match the chain");
+            }
+            height -= 1;
+        };
+
+        let removed = self.sink.delete_blocks_after(ancestor).await?;
+        progress.rewind(ancestor);
+        self.publish_checkpoint(progress);
+        self.sink.set_progress(progress).await?;
+
+        warn!(
+            "Reorg handled: removed {} orphaned block(s), rewound checkpoint to block {} (depth {})",
+            removed,
+            ancestor,
+            tip - ancestor
+        );
+
+        Ok(())
+    }
+
+    /// Index a contiguous range of blocks, returning the blocks that could not be indexed
+    async fn index_block_range(&self, from: u64, to: u64) -> Vec<u64> {
+        let block_numbers: Vec<u64> = (from..=to).collect();
+        self.index_blocks(&block_numbers).await
+    }
+
+    /// Index the given blocks, returning the ones that failed to fetch or to write
+    async fn index_blocks(&self, block_numbers: &[u64]) -> Vec<u64> {
+        let mut indexed_blocks = Vec::new();
+        let mut failed_blocks = Vec::new();
+
+        // Process blocks in parallel; the adaptive limit decides how many run at once
+        let total_blocks = block_numbers.len();
+        let last_block = block_numbers.last().copied().unwrap_or(0);
+
+        let results: Vec<(u64, Result<IndexedBlock>)> =
+            stream::iter(block_numbers.chunks(self.config.rpc_batch_size))
+                .map(|chunk| async move {
+                    let _permit = self.concurrency.acquire().await;
+                    self.fetch_blocks(chunk).await
+                })
+                .buffer_unordered(self.config.max_concurrency)
+                .flat_map(stream::iter)
+                .collect()
+                .await;
 
         // Sort by block number to maintain order
         let mut sorted_results: Vec<_> = results.into_iter().collect();
         sorted_results.sort_by_key(|(block_num, _)| *block_num);
 
+        let (safe, finalized) = self.finality_heads().await;
+
         // Collect successful results and prepare for bulk indexing
         for (block_num, result) in sorted_results {
             match result {
-                Ok(block) => {
+                Ok(mut block) => {
+                    block.finality = FinalityStatus::for_block(block_num, safe, finalized);
                     indexed_blocks.push(block);
-                    if block_num % 100 == 0 || block_num == to {
+                    if block_num % 100 == 0 || block_num == last_block {
                         debug!(
                             "Processed block {} (progress: {}/{})",
                             block_num,
@@ -270,117 +1277,411 @@ impl BlockIndexer {
                     }
                 }
                 Err(e) => {
-                    error_count += 1;
-                    error!("Error processing block {}: {}", block_num, e);
+                    error!(
+                        "Error processing block {} ({}): {:#}",
+                        block_num,
+                        rpc::error_kind(&e),
+                        e
+                    );
+                    METRICS
+                        .block_failures
+                        .with_label_values(&[rpc::error_kind(&e)])
+                        .inc();
+                    failed_blocks.push(block_num);
                 }
             }
         }
 
+        let fetch_errors = failed_blocks.len();
+
         // Bulk index all blocks at once
         if !indexed_blocks.is_empty() {
             // Index in chunks of es_bulk_size
             for chunk in indexed_blocks.chunks(self.config.es_bulk_size) {
-                if let Err(e) = self.es_client.bulk_index_blocks(chunk).await {
-                    error!("Error bulk indexing blocks: {}", e);
-                    // Fallback to individual indexing
-                    for block in chunk {
-                        if let Err(e) = self.es_client.index_block(block).await {
-                            error!("Error indexing block {}: {}", block.number, e);
+                match self.sink.write_blocks(chunk).await {
+                    Ok(_) => self.record_indexed(chunk),
+                    Err(e) => {
+                        error!("Error bulk indexing blocks: {}", e);
+                        // Fallback to individual indexing
+                        for block in chunk {
+                            match self.sink.write_blocks(std::slice::from_ref(block)).await {
+                                Ok(_) => self.record_indexed(std::slice::from_ref(block)),
+                                Err(e) => {
+                                    error!("Error indexing block {}: {}", block.number, e);
+                                    METRICS
+                                        .block_failures
+                                        .with_label_values(&[rpc::error_kind(&e)])
+                                        .inc();
+                                    failed_blocks.push(block.number);
+                                }
+                            }
                         }
                     }
                 }
             }
         }
 
-        let success_count = indexed_blocks.len();
-        if error_count > 0 {
+        if !failed_blocks.is_empty() {
             warn!(
-                "Batch completed with {} errors out of {} blocks",
-                error_count,
-                success_count + error_count
+                "Batch completed with {} errors out of {} blocks ({} fetch, {} write)",
+                failed_blocks.len(),
+                total_blocks,
+                fetch_errors,
+                failed_blocks.len() - fetch_errors
             );
         }
 
-        Ok(())
+        failed_blocks.sort_unstable();
+        failed_blocks
     }
 
-    async fn index_block_internal(
-        block_number: u64,
-        provider: &Arc<Provider<Http>>,
-    ) -> Result<IndexedBlock> {
-        // OPTIMIZATION: Use get_block_with_txs to get block with full transactions in one RPC call
-        // This eliminates N additional get_transaction calls (where N = number of transactions)
-        let block_opt: Option<Block<Transaction>> = provider
-            .get_block_with_txs(block_number)
+    /// Fetch a block and its receipts from the RPC pool. Both come from the same node, so a
+    /// failure on either fails over to another node as a unit.
+    async fn fetch_block(&self, block_number: u64) -> Result<IndexedBlock> {
+        let fetch_receipts = self.config.index_receipts;
+        self.rpc
+            .call(|endpoint| async move {
+                Self::index_block_internal(block_number, &endpoint, fetch_receipts).await
+            })
             .await
-            .context("Failed to fetch block from RPC")?;
+    }
 
-        let block = block_opt.context("Block not found")?;
+    /// Fetch several blocks from the RPC pool with JSON-RPC batch requests
+    async fn fetch_blocks(&self, block_numbers: &[u64]) -> Vec<(u64, Result<IndexedBlock>)> {
+        if let [block_number] = block_numbers {
+            return vec![(*block_number, self.fetch_block(*block_number).await)];
+        }
 
-        // Transactions are already included in the block, no need for separate RPC calls
-        let transactions: Vec<IndexedTransaction> = block
-            .transactions
+        let fetch_receipts = self.config.index_receipts;
+        let batch = self
+            .rpc
+            .call(|endpoint| async move {
+                Self::index_block_batch(block_numbers, &endpoint, fetch_receipts).await
+            })
+            .await;
+
+        match batch {
+            Ok(results) => {
+                let mut fetched = Vec::with_capacity(results.len());
+                for (&block_number, result) in block_numbers.iter().zip(results) {
+                    // Blocks that failed transiently inside the batch are retried on their own
+                    let result = match result {
+                        Err(e) if rpc::is_transient(&e) => self.fetch_block(block_number).await,
+                        result => result,
+                    };
+                    fetched.push((block_number, result));
+                }
+                fetched
+            }
+            Err(e) => {
+                // Fetched one by one, each block fails with its own typed error
+                warn!(
+                    "Batch of {} block(s) failed, fetching them individually: {:#}",
+                    block_numbers.len(),
+                    e
+                );
+                let mut fetched = Vec::with_capacity(block_numbers.len());
+                for &block_number in block_numbers {
+                    fetched.push((block_number, self.fetch_block(block_number).await));
+                }
+                fetched
+            }
+        }
+    }
+
+    /// Fetch blocks and their receipts from one node, batching `eth_getBlockByNumber` and
+    /// `eth_getBlockReceipts`. Only a failure of the whole batch is returned as an error.
+    async fn index_block_batch(
+        block_numbers: &[u64],
+        endpoint: &RpcEndpoint,
+        fetch_receipts: bool,
+    ) -> Result<Vec<Result<IndexedBlock>>> {
+        let transport = endpoint.provider.as_ref();
+
+        let params = block_numbers
             .iter()
-            .enumerate()
-            .map(|(idx, tx)| IndexedTransaction {
-                hash: format!("{:?}", tx.hash),
-                from: format!("{:?}", tx.from),
-                to: tx.to.map(|a| format!("{:?}", a)),
-                value: tx.value.to_string(),
-                gas: tx.gas.as_u64(),
-                gas_price: tx
-                    .gas_price
-                    .map(|p: U256| p.to_string())
-                    .unwrap_or_else(|| "0".to_string()),
-                input: hex::encode(tx.input.as_ref()),
-                nonce: tx.nonce.as_u64(),
-                transaction_index: Some(idx as u64),
+            .map(|&block_number| json!([U64::from(block_number), true]))
+            .collect();
+        let blocks: Vec<Result<Block<Transaction>>> = transport
+            .batch_request::<Option<Block<Transaction>>>("eth_getBlockByNumber", params)
+            .await
+            .map_err(IndexerError::from)
+            .context("Failed to fetch blocks from RPC")?
+            .into_iter()
+            .zip(block_numbers)
+            .map(|(block, &block_number)| {
+                block
+                    .map_err(IndexerError::from)
+                    .context("Failed to fetch block from RPC")?
+                    .ok_or(IndexerError::BlockNotFound(block_number).into())
             })
             .collect();
 
-        Self::convert_block_from_full(block, transactions).await
+        let mut receipts: Vec<Option<Vec<TransactionReceipt>>> = vec![None; blocks.len()];
+        if fetch_receipts && endpoint.block_receipts_supported.load(Ordering::Relaxed) {
+            let with_transactions: Vec<usize> = blocks
+                .iter()
+                .enumerate()
+                .filter(|(_, block)| matches!(block, Ok(block) if !block.transactions.is_empty()))
+                .map(|(index, _)| index)
+                .collect();
+            let params = with_transactions
+                .iter()
+                .map(|&index| json!([U64::from(block_numbers[index])]))
+                .collect();
+            let batched = transport
+                .batch_request::<Vec<TransactionReceipt>>("eth_getBlockReceipts", params)
+                .await
+                .map_err(IndexerError::from)
+                .context("Failed to fetch block receipts from RPC")?;
+            for (index, block_receipts) in with_transactions.into_iter().zip(batched) {
+                receipts[index] = block_receipts.ok();
+            }
+        }
+
+        let mut results = Vec::with_capacity(blocks.len());
+        for (block, block_receipts) in blocks.into_iter().zip(receipts) {
+            let result = async {
+                let block = block?;
+                let receipts = match block_receipts {
+                    Some(receipts) if receipts.len() == block.transactions.len() => receipts
+                        .into_iter()
+                        .map(|r| (r.transaction_hash, r))
+                        .collect(),
+                    // Not batched or not usable: fetch them like for a single block
+                    _ if fetch_receipts && !block.transactions.is_empty() => {
+                        Self::fetch_receipts(&block, endpoint).await?
+                    }
+                    _ => HashMap::new(),
+                };
+                Self::build_indexed_block(block, receipts).await
+            }
+            .await;
+            results.push(result);
+        }
+
+        Ok(results)
     }
 
-    async fn index_block(&self, block_number: u64) -> Result<()> {
+    async fn index_block_internal(
+        block_number: u64,
+        endpoint: &RpcEndpoint,
+        fetch_receipts: bool,
+    ) -> Result<IndexedBlock> {
         // OPTIMIZATION: Use get_block_with_txs to get block with full transactions in one RPC call
-        let block_opt: Option<Block<Transaction>> = self
+        // This eliminates N additional get_transaction calls (where N = number of transactions)
+        let block_opt: Option<Block<Transaction>> = endpoint
             .provider
             .get_block_with_txs(block_number)
             .await
+            .map_err(IndexerError::from)
             .context("Failed to fetch block from RPC")?;
 
-        let block = block_opt.context("Block not found")?;
+        let block = block_opt.ok_or(IndexerError::BlockNotFound(block_number))?;
+
+        let receipts = if fetch_receipts && !block.transactions.is_empty() {
+            Self::fetch_receipts(&block, endpoint).await?
+        } else {
+            HashMap::new()
+        };
+
+        Self::build_indexed_block(block, receipts).await
+    }
 
-        // Transactions are already included in the block
+    /// Convert a block and its receipts, keyed by transaction hash, into the indexed document
+    async fn build_indexed_block(
+        block: Block<Transaction>,
+        receipts: HashMap<H256, TransactionReceipt>,
+    ) -> Result<IndexedBlock> {
+        // Transactions are already included in the block, no need for separate RPC calls
         let transactions: Vec<IndexedTransaction> = block
             .transactions
             .iter()
             .enumerate()
-            .map(|(idx, tx)| IndexedTransaction {
-                hash: format!("{:?}", tx.hash),
-                from: format!("{:?}", tx.from),
-                to: tx.to.map(|a| format!("{:?}", a)),
-                value: tx.value.to_string(),
-                gas: tx.gas.as_u64(),
-                gas_price: tx
-                    .gas_price
-                    .map(|p: U256| p.to_string())
-                    .unwrap_or_else(|| "0".to_string()),
-                input: hex::encode(tx.input.as_ref()),
-                nonce: tx.nonce.as_u64(),
-                transaction_index: Some(idx as u64),
+            .map(|(idx, tx)| {
+                Self::convert_transaction(idx, tx, receipts.get(&tx.hash), block.base_fee_per_gas)
             })
             .collect();
 
-        let indexed_block = Self::convert_block_from_full(block, transactions).await?;
-        self.es_client.index_block(&indexed_block).await?;
+        // Logs come from the receipts, in transaction order
+        let logs: Vec<IndexedLog> = block
+            .transactions
+            .iter()
+            .filter_map(|tx| receipts.get(&tx.hash))
+            .flat_map(|receipt| receipt.logs.iter())
+            .map(|log| Self::convert_log(log, &block))
+            .collect();
 
-        Ok(())
+        Self::convert_block_from_full(block, transactions, logs).await
+    }
+
+    /// Fetch every receipt of a block with `eth_getBlockReceipts`, falling back to one
+    /// `eth_getTransactionReceipt` call per transaction when the node does not support it
+    async fn fetch_receipts(
+        block: &Block<Transaction>,
+        endpoint: &RpcEndpoint,
+    ) -> Result<HashMap<H256, TransactionReceipt>> {
+        let block_number = block
+            .number
+            .ok_or_else(|| IndexerError::InvalidResponse("block has no number".to_string()))?;
+        let provider = &endpoint.provider;
+        let block_receipts_supported = &endpoint.block_receipts_supported;
+
+        if block_receipts_supported.load(Ordering::Relaxed) {
+            match provider.get_block_receipts(block_number).await {
+                Ok(receipts) if receipts.len() == block.transactions.len() => {
+                    return Ok(receipts
+                        .into_iter()
+                        .map(|r| (r.transaction_hash, r))
+                        .collect());
+                }
+                Ok(receipts) => {
+                    warn!(
+                        "eth_getBlockReceipts returned {} receipt(s) for {} transaction(s) in block {}, fetching individually",
+                        receipts.len(),
+                        block.transactions.len(),
+                        block_number
+                    );
+                }
+                Err(e) => match IndexerError::from(e) {
+                    rejected @ IndexerError::RpcRejected { .. } => {
+                        warn!(
+                            "eth_getBlockReceipts is not supported by {} ({}), falling back to eth_getTransactionReceipt",
+                            endpoint.url, rejected
+                        );
+                        block_receipts_supported.store(false, Ordering::Relaxed);
+                    }
+                    e => return Err(e).context("Failed to fetch block receipts from RPC"),
+                },
+            }
+        }
+
+        let receipts =
+            futures::future::try_join_all(block.transactions.iter().map(|tx| async move {
+                provider
+                    .get_transaction_receipt(tx.hash)
+                    .await
+                    .map_err(IndexerError::from)
+                    .context("Failed to fetch transaction receipt from RPC")?
+                    .ok_or_else(|| {
+                        anyhow::Error::from(IndexerError::InvalidResponse(format!(
+                            "receipt not found for transaction {:?}",
+                            tx.hash
+                        )))
+                    })
+            }))
+            .await?;
+
+        Ok(receipts
+            .into_iter()
+            .map(|r| (r.transaction_hash, r))
+            .collect())
+    }
+
+    fn convert_transaction(
+        idx: usize,
+        tx: &Transaction,
+        receipt: Option<&TransactionReceipt>,
+        base_fee: Option<U256>,
+    ) -> IndexedTransaction {
+        let effective_gas_price = receipt.and_then(|r| r.effective_gas_price);
+
+        IndexedTransaction {
+            hash: format!("{:?}", tx.hash),
+            from: format!("{:?}", tx.from),
+            to: tx.to.map(|a| format!("{:?}", a)),
+            value: tx.value.to_string(),
+            gas: tx.gas.as_u64(),
+            // Post-London nodes may omit gasPrice, so derive the price actually paid
+            gas_price: tx
+                .gas_price
+                .or(effective_gas_price)
+                .or_else(|| Self::dynamic_fee_gas_price(tx, base_fee))
+                .map(|p: U256| p.to_string())
+                .unwrap_or_else(|| "0".to_string()),
+            input: hex::encode(tx.input.as_ref()),
+            nonce: tx.nonce.as_u64(),
+            transaction_index: Some(idx as u64),
+            status: receipt.and_then(|r| r.status).map(|s| s.as_u64()),
+            gas_used: receipt.and_then(|r| r.gas_used).map(|g| g.as_u64()),
+            cumulative_gas_used: receipt.map(|r| r.cumulative_gas_used.as_u64()),
+            effective_gas_price: effective_gas_price.map(|p| p.to_string()),
+            contract_address: receipt
+                .and_then(|r| r.contract_address)
+                .map(|a| format!("{:?}", a)),
+            logs_bloom: receipt.map(|r| format!("{:?}", r.logs_bloom)),
+            transaction_type: tx.transaction_type.map(|t| t.as_u64()),
+            max_fee_per_gas: tx.max_fee_per_gas.map(|f| f.to_string()),
+            max_priority_fee_per_gas: tx.max_priority_fee_per_gas.map(|f| f.to_string()),
+            max_fee_per_blob_gas: tx
+                .other
+                .get_deserialized::<U256>("maxFeePerBlobGas")
+                .and_then(|f| f.ok())
+                .map(|f| f.to_string()),
+            access_list: tx.access_list.as_ref().map(|list| {
+                list.0
+                    .iter()
+                    .map(|item| IndexedAccessListItem {
+                        address: format!("{:?}", item.address),
+                        storage_keys: item
+                            .storage_keys
+                            .iter()
+                            .map(|k| format!("{:?}", k))
+                            .collect(),
+                    })
+                    .collect()
+            }),
+            chain_id: tx.chain_id.map(|c| c.as_u64()),
+            blob_versioned_hashes: tx
+                .other
+                .get_deserialized::<Vec<H256>>("blobVersionedHashes")
+                .and_then(|h| h.ok())
+                .map(|hashes| hashes.iter().map(|h| format!("{:?}", h)).collect()),
+        }
+    }
+
+    /// Price paid by an EIP-1559 transaction: `min(max_fee, base_fee + max_priority_fee)`
+    fn dynamic_fee_gas_price(tx: &Transaction, base_fee: Option<U256>) -> Option<U256> {
+        let max_fee = tx.max_fee_per_gas?;
+        let priority_fee = tx.max_priority_fee_per_gas?;
+        Some(max_fee.min(base_fee?.saturating_add(priority_fee)))
+    }
+
+    fn convert_log(log: &Log, block: &Block<Transaction>) -> IndexedLog {
+        let topic = |i: usize| log.topics.get(i).map(|t| format!("{:?}", t));
+
+        IndexedLog {
+            address: format!("{:?}", log.address),
+            topic0: topic(0),
+            topic1: topic(1),
+            topic2: topic(2),
+            topic3: topic(3),
+            data: hex::encode(log.data.as_ref()),
+            log_index: log.log_index.map(|i| i.as_u64()).unwrap_or(0),
+            transaction_hash: log
+                .transaction_hash
+                .map(|h| format!("{:?}", h))
+                .unwrap_or_default(),
+            transaction_index: log.transaction_index.map(|i| i.as_u64()).unwrap_or(0),
+            block_number: log
+                .block_number
+                .or(block.number)
+                .map(|n| n.as_u64())
+                .unwrap_or(0),
+            block_hash: log
+                .block_hash
+                .or(block.hash)
+                .map(|h| format!("{:?}", h))
+                .unwrap_or_default(),
+            timestamp: block.timestamp.as_u64(),
+            removed: log.removed.unwrap_or(false),
+        }
     }
 
     async fn convert_block_from_full(
         block: Block<Transaction>,
         transactions: Vec<IndexedTransaction>,
+        logs: Vec<IndexedLog>,
     ) -> Result<IndexedBlock> {
         let indexed_at = SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -405,6 +1706,378 @@ impl BlockIndexer {
             transactions,
             uncles: block.uncles.len(),
             indexed_at,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: block.base_fee_per_gas.map(|f| f.to_string()),
+            withdrawals_root: block.withdrawals_root.map(|r| format!("{:?}", r)),
+            blob_gas_used: block.blob_gas_used.map(|g| g.as_u64()),
+            excess_blob_gas: block.excess_blob_gas.map(|g| g.as_u64()),
+            withdrawals: block
+                .withdrawals
+                .unwrap_or_default()
+                .into_iter()
+                .map(|w| IndexedWithdrawal {
+                    index: w.index.as_u64(),
+                    validator_index: w.validator_index.as_u64(),
+                    address: format!("{:?}", w.address),
+                    amount: w.amount.as_u64(),
+                })
+                .collect(),
+            logs,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+    use crate::sink::MemorySink;
+
+    /// An indexer on top of `sink` whose RPC node is unreachable
+    async fn indexer(sink: &MemorySink, skip_blocks: &str) -> BlockIndexer {
+        indexer_with(Box::new(sink.clone()), &[("SKIP_BLOCKS", skip_blocks)]).await
+    }
+
+    /// An indexer on top of `sink` with the given settings on top of the test defaults
+    async fn indexer_with(sink: Box<dyn Sink>, settings: &[(&str, &str)]) -> BlockIndexer {
+        let mut vars = vec![("RPC_TIMEOUT_SECS", "1")];
+        vars.extend(settings);
+        let config = Config::for_test(&vars);
+        BlockIndexer::with_sink(config, sink).await.unwrap()
+    }
+
+    /// Hash of block `number` on the canonical chain, the one `test_block` stores
+    fn canonical_hash(number: u64) -> H256 {
+        H256::from_low_u64_be(number)
+    }
+
+    /// A stored block that a reorg replaced
+    fn orphaned_block(number: u64) -> IndexedBlock {
+        let mut block = test_block(number);
+        block.hash = format!("{:?}", H256::from_low_u64_be(number + 500_000));
+        block
+    }
+
+    /// Answer one JSON-RPC request from a chain of empty blocks with the given hashes
+    fn rpc_response(request: &serde_json::Value, hashes: &[H256]) -> serde_json::Value {
+        let head = hashes.len() as u64 - 1;
+        let result = match request["method"].as_str() {
+            Some("eth_blockNumber") => json!(U64::from(head)),
+            Some("eth_getBlockByNumber") => {
+                // Tags such as `safe` or `finalized` all resolve to the head
+                let number = request["params"][0]
+                    .as_str()
+                    .and_then(|n| n.strip_prefix("0x"))
+                    .map_or(head, |n| u64::from_str_radix(n, 16).unwrap());
+                let Some(hash) = hashes.get(number as usize) else {
+                    return json!({ "jsonrpc": "2.0", "id": request["id"], "result": null });
+                };
+                // One transaction per block, the one `test_block` stores
+                let transaction = Transaction {
+                    hash: H256::from_low_u64_be(number + 1_000_000),
+                    block_hash: Some(*hash),
+                    block_number: Some(number.into()),
+                    transaction_index: Some(0.into()),
+                    ..Default::default()
+                };
+                let mut block = json!(Block::<Transaction> {
+                    number: Some(number.into()),
+                    hash: Some(*hash),
+                    parent_hash: number
+                        .checked_sub(1)
+                        .map_or(H256::zero(), |parent| hashes[parent as usize]),
+                    transactions: vec![transaction.clone()],
+                    ..Default::default()
+                });
+                if request["params"][1] != json!(true) {
+                    block["transactions"] = json!([transaction.hash]);
+                }
+                block
+            }
+            _ => serde_json::Value::Null,
+        };
+        json!({ "jsonrpc": "2.0", "id": request["id"], "result": result })
+    }
+
+    /// A JSON-RPC node serving `hashes`, which the test may change to simulate a reorg
+    async fn mock_node(hashes: Arc<std::sync::Mutex<Vec<H256>>>) -> mockito::ServerGuard {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("POST", "/")
+            .with_header("content-type", "application/json")
+            .with_body_from_request(move |request| {
+                let body: serde_json::Value =
+                    serde_json::from_slice(request.body().unwrap()).unwrap();
+                let hashes = hashes.lock().unwrap();
+                let response = match body {
+                    serde_json::Value::Array(batch) => batch
+                        .iter()
+                        .map(|request| rpc_response(request, &hashes))
+                        .collect(),
+                    request => rpc_response(&request, &hashes),
+                };
+                serde_json::to_vec(&response).unwrap()
+            })
+            .create_async()
+            .await;
+        server
+    }
+
+    /// An indexer on top of `sink` following a mock node with `length` canonical blocks
+    async fn indexer_on_chain(
+        sink: &MemorySink,
+        length: u64,
+        settings: &[(&str, &str)],
+    ) -> (BlockIndexer, mockito::ServerGuard) {
+        sink_indexer_on_chain(Box::new(sink.clone()), length, settings).await
+    }
+
+    async fn sink_indexer_on_chain(
+        sink: Box<dyn Sink>,
+        length: u64,
+        settings: &[(&str, &str)],
+    ) -> (BlockIndexer, mockito::ServerGuard) {
+        let hashes = (0..length).map(canonical_hash).collect();
+        let node = mock_node(Arc::new(std::sync::Mutex::new(hashes))).await;
+        let url = node.url();
+        let mut settings = settings.to_vec();
+        settings.extend([("RPC_HTTP_URL", url.as_str()), ("INDEX_RECEIPTS", "false")]);
+        (indexer_with(sink, &settings).await, node)
+    }
+
+    #[tokio::test]
+    async fn test_failed_batch_keeps_typed_errors() {
+        let hashes: Vec<H256> = (0..10).map(canonical_hash).collect();
+        let mut node = mockito::Server::new_async().await;
+        // The node rejects every batch but answers single requests
+        let batches = node
+            .mock("POST", "/")
+            .match_body(mockito::Matcher::Regex(r"^\s*\[".to_string()))
+            .with_status(500)
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        node.mock("POST", "/")
+            .match_body(mockito::Matcher::Regex(r"^\s*\{".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body_from_request(move |request| {
+                let body: serde_json::Value =
+                    serde_json::from_slice(request.body().unwrap()).unwrap();
+                serde_json::to_vec(&rpc_response(&body, &hashes)).unwrap()
+            })
+            .create_async()
+            .await;
+        let url = node.url();
+        let sink = MemorySink::default();
+        let indexer = indexer_with(
+            Box::new(sink),
+            &[
+                ("RPC_HTTP_URL", url.as_str()),
+                ("INDEX_RECEIPTS", "false"),
+                ("RPC_MAX_RETRIES", "0"),
+                ("RPC_BATCH_SIZE", "10"),
+            ],
+        )
+        .await;
+
+        let fetched = indexer.fetch_blocks(&[3, 4, 50]).await;
+        assert_eq!(fetched[0].1.as_ref().unwrap().number, 3);
+        assert_eq!(fetched[1].1.as_ref().unwrap().number, 4);
+        let error = fetched[2].1.as_ref().unwrap_err();
+        assert_eq!(rpc::error_kind(error), "block_not_found");
+        batches.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_find_gaps() {
+        let sink = MemorySink::default();
+        let blocks: Vec<IndexedBlock> = (0..=2000)
+            .filter(|n| !(500..=520).contains(n) && *n != 1999)
+            .map(test_block)
+            .collect();
+        sink.write_blocks(&blocks).await.unwrap();
+
+        let indexer = indexer(&sink, "").await;
+        assert_eq!(
+            indexer.find_gaps(0, 2000).await.unwrap(),
+            vec![(500, 520), (1999, 1999)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_progress_skips_and_resets() {
+        let sink = MemorySink::default();
+        let mut progress = SyncProgress::from_checkpoint(0);
+        progress.record(200, &[50, 150]);
+        sink.set_progress(&progress).await.unwrap();
+
+        let indexer = indexer(&sink, "50").await;
+        let loaded = indexer.load_progress().await.unwrap();
+        assert_eq!(loaded.checkpoint(), 149);
+        assert_eq!(sink.progress().unwrap().checkpoint(), 149);
+        assert_eq!(indexer.sync_state().checkpoint, 149);
+
+        indexer.reset_checkpoint(100).await.unwrap();
+        let reset = sink.progress().unwrap();
+        assert_eq!(reset.scanned_through, 100);
+        assert!(reset.failed_blocks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_backfill_range_leaves_checkpoint() {
+        let sink = MemorySink::default();
+        sink.set_progress(&SyncProgress::from_checkpoint(10))
+            .await
+            .unwrap();
+        let (indexer, _node) = indexer_on_chain(&sink, 100, &[]).await;
+
+        // Above the checkpoint: the blocks in between are still to be indexed
+        indexer.backfill_range(50, Some(60)).await.unwrap();
+        assert_eq!(sink.block_numbers(), (50..=60).collect::<Vec<_>>());
+        assert_eq!(sink.progress().unwrap().scanned_through, 10);
+
+        // Below the checkpoint, up to the head by default
+        sink.set_progress(&SyncProgress::from_checkpoint(95))
+            .await
+            .unwrap();
+        indexer.backfill_range(90, None).await.unwrap();
+        assert_eq!(sink.block_numbers().last(), Some(&99));
+        assert!(sink.block_numbers().contains(&90));
+        assert_eq!(sink.progress().unwrap(), SyncProgress::from_checkpoint(95));
+    }
+
+    #[tokio::test]
+    async fn test_live_sync_checks_the_stored_parent() {
+        let sink = MemorySink::default();
+        let mut blocks: Vec<IndexedBlock> = (0..10).map(test_block).collect();
+        blocks.push(orphaned_block(10));
+        sink.write_blocks(&blocks).await.unwrap();
+        let mut progress = SyncProgress::from_checkpoint(10);
+        let (indexer, _node) = indexer_on_chain(&sink, 13, &[]).await;
+
+        indexer.sync_new_blocks(&mut progress, None).await.unwrap();
+        assert_eq!(progress, SyncProgress::from_checkpoint(9));
+        assert_eq!(sink.block_numbers(), (0..=9).collect::<Vec<_>>());
+
+        indexer.sync_new_blocks(&mut progress, None).await.unwrap();
+        assert_eq!(progress, SyncProgress::from_checkpoint(12));
+        assert_eq!(
+            sink.get_block(10).await.unwrap().unwrap().hash,
+            format!("{:?}", canonical_hash(10))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retried_block_detects_reorg_around_it() {
+        let sink = MemorySink::default();
+        let blocks: Vec<IndexedBlock> = (0..=10)
+            .filter(|n| *n != 5)
+            .map(|n| {
+                if n == 4 {
+                    orphaned_block(n)
+                } else {
+                    test_block(n)
+                }
+            })
+            .collect();
+        sink.write_blocks(&blocks).await.unwrap();
+        let mut progress = SyncProgress::from_checkpoint(0);
+        progress.record(10, &[5]);
+        let (indexer, _node) = indexer_on_chain(&sink, 20, &[]).await;
+
+        indexer.retry_failed_blocks(&mut progress).await.unwrap();
+        assert_eq!(progress, SyncProgress::from_checkpoint(3));
+        assert_eq!(sink.progress().unwrap(), progress);
+        assert_eq!(sink.block_numbers(), (0..=3).collect::<Vec<_>>());
+    }
+
+    /// A sink holding blocks `0..=tip` whose blocks from `fork` on were orphaned by a reorg
+    async fn forked_sink(fork: u64, tip: u64) -> MemorySink {
+        let sink = MemorySink::default();
+        let blocks: Vec<IndexedBlock> = (0..=tip)
+            .map(|n| {
+                if n >= fork {
+                    orphaned_block(n)
+                } else {
+                    test_block(n)
+                }
+            })
+            .collect();
+        sink.write_blocks(&blocks).await.unwrap();
+        sink
+    }
+
+    #[tokio::test]
+    async fn test_reorg_of_one_block() {
+        let sink = forked_sink(10, 10).await;
+        let mut progress = SyncProgress::from_checkpoint(10);
+        let (indexer, _node) = indexer_on_chain(&sink, 20, &[]).await;
+
+        indexer.handle_reorg(10, &mut progress).await.unwrap();
+        assert_eq!(progress, SyncProgress::from_checkpoint(9));
+        assert_eq!(sink.progress().unwrap(), progress);
+        assert_eq!(sink.block_numbers(), (0..=9).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_of_several_blocks() {
+        let sink = forked_sink(7, 10).await;
+        let mut progress = SyncProgress::from_checkpoint(10);
+        let (indexer, _node) = indexer_on_chain(&sink, 20, &[]).await;
+
+        indexer.handle_reorg(10, &mut progress).await.unwrap();
+        assert_eq!(progress, SyncProgress::from_checkpoint(6));
+        assert_eq!(sink.block_numbers(), (0..=6).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_deeper_than_max_depth_fails() {
+        let sink = forked_sink(5, 10).await;
+        let mut progress = SyncProgress::from_checkpoint(10);
+        let (indexer, _node) = indexer_on_chain(&sink, 20, &[("MAX_REORG_DEPTH", "3")]).await;
+
+        assert!(indexer.handle_reorg(10, &mut progress).await.is_err());
+        assert_eq!(progress, SyncProgress::from_checkpoint(10));
+        assert!(sink.progress().is_none());
+        assert_eq!(sink.block_numbers(), (0..=10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_reorg_forgets_failures_above_the_ancestor() {
+        let sink = forked_sink(8, 10).await;
+        let mut progress = SyncProgress::from_checkpoint(0);
+        progress.record(10, &[3, 9]);
+        let (indexer, _node) = indexer_on_chain(&sink, 20, &[]).await;
+
+        indexer.handle_reorg(10, &mut progress).await.unwrap();
+        assert_eq!(progress.scanned_through, 7);
+        assert_eq!(
+            progress.failed_blocks.iter().copied().collect::<Vec<_>>(),
+            vec![3]
+        );
+        assert_eq!(sink.progress().unwrap(), progress);
+    }
+
+    #[tokio::test]
+    async fn test_verify_checks_every_sink() {
+        let (a, b) = (MemorySink::default(), MemorySink::default());
+        let blocks: Vec<IndexedBlock> = (0..10).map(test_block).collect();
+        a.write_blocks(&blocks).await.unwrap();
+        b.write_blocks(&blocks).await.unwrap();
+        b.write_blocks(&[orphaned_block(5)]).await.unwrap();
+        let fan_out = sink::FanOutSink::new(vec![Box::new(a.clone()), Box::new(b.clone())]);
+        let (indexer, _node) = sink_indexer_on_chain(Box::new(fan_out), 20, &[]).await;
+
+        let options = VerifyOptions {
+            from: Some(0),
+            to: Some(9),
+            sample: None,
+            fix: true,
+        };
+        indexer.verify(options).await.unwrap();
+        assert_eq!(
+            b.get_block(5).await.unwrap().unwrap().hash,
+            format!("{:?}", canonical_hash(5))
+        );
+    }
+}
@@ -1,27 +1,66 @@
 //! Blockchain Indexer
 //!
-//! A Rust service that indexes blockchain blocks and transactions into Elasticsearch.
+//! A Rust service that indexes blockchain blocks and transactions into the configured sinks.
 //! Supports both historical backfill and real-time synchronization.
 
+mod cli;
 mod config;
 mod elasticsearch;
 mod error;
+mod gaps;
+mod health;
 mod indexer;
+mod metrics;
 mod models;
+mod ndjson;
+mod parquet;
+mod postgres;
+mod progress;
+mod rpc;
+mod server;
+mod shutdown;
+mod sink;
+mod sql;
+mod sqlite;
+mod throttle;
+mod transport;
+mod verify;
 
 use anyhow::Result;
+use clap::Parser;
+use cli::{Cli, Command, ConfigCommand};
 use config::Config;
 use indexer::BlockIndexer;
-use log::info;
+use log::{error, info};
+use std::sync::Arc;
+use verify::VerifyOptions;
 
 /// Main entry point for the blockchain indexer
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Parse arguments first so `--help` and usage errors are printed on their own
+    let cli = Cli::parse();
+    let command = cli.command.clone().unwrap_or(Command::Run);
+
     // Force immediate output to stdout/stderr (no buffering)
     use std::io::Write;
 
+    // The configuration decides where logs go, so it is loaded before the logger
+    let config = match load_config(&cli) {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("ERROR: Failed to load configuration: {}", e);
+            std::io::stderr().flush().ok();
+            std::process::exit(1);
+        }
+    };
+    // NDJSON documents on stdout must not be mixed with logs
+    let logs_to_stdout = !config.stdout_is_output();
+
     // Escribir directamente a stdout/stderr sin buffering
-    println!("=== Blockchain Indexer Starting ===");
+    if logs_to_stdout {
+        println!("=== Blockchain Indexer Starting ===");
+    }
     eprintln!("=== Blockchain Indexer Starting (stderr) ===");
     std::io::stdout().flush().ok();
     std::io::stderr().flush().ok();
@@ -29,8 +68,10 @@ async fn main() -> Result<()> {
     // Initialize logger with default level if RUST_LOG is not set
     // Railway necesita logs en stdout sin buffering
     let rust_log = std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string());
-    println!("RUST_LOG={}", rust_log);
-    std::io::stdout().flush().ok();
+    if logs_to_stdout {
+        println!("RUST_LOG={}", rust_log);
+        std::io::stdout().flush().ok();
+    }
 
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
         .format(|buf, record| {
@@ -38,13 +79,18 @@ async fn main() -> Result<()> {
             writeln!(buf, "[{}] {}", record.level(), record.args())
         })
         .format_timestamp_secs()
-        .target(env_logger::Target::Stdout)
+        .target(if logs_to_stdout {
+            env_logger::Target::Stdout
+        } else {
+            env_logger::Target::Stderr
+        })
         .init();
 
     info!("Starting Blockchain Indexer...");
+    info!("Configuration loaded successfully");
     std::io::stdout().flush().ok();
 
-    if let Err(e) = run().await {
+    if let Err(e) = run(command, config).await {
         eprintln!("Fatal error: {:?}", e);
         eprintln!("Error details: {}", e);
         std::io::stderr().flush().ok();
@@ -54,33 +100,40 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run() -> Result<()> {
-    use std::io::Write;
+fn load_config(cli: &Cli) -> Result<Config> {
+    Config::load(cli.config.as_deref(), &cli.overrides.vars())
+}
 
-    info!("Loading configuration from environment variables...");
-    std::io::stdout().flush().ok();
+async fn run(command: Command, config: Config) -> Result<()> {
+    use std::io::Write;
 
-    let config = match Config::from_env() {
-        Ok(c) => {
-            info!("Configuration loaded successfully");
-            std::io::stdout().flush().ok();
-            c
+    if let Command::Config {
+        command: ConfigCommand::Check,
+    } = command
+    {
+        for (name, value) in config.redacted_settings() {
+            println!("{}={}", name, value);
         }
-        Err(e) => {
-            eprintln!("ERROR: Failed to load configuration: {}", e);
-            std::io::stderr().flush().ok();
-            return Err(e);
-        }
-    };
+        std::io::stdout().flush().ok();
+        return Ok(());
+    }
+
+    // Connecting creates any missing index
+    if command == Command::InitIndices {
+        sink::connect(&config).await?;
+        info!("Indices are ready");
+        return Ok(());
+    }
 
     info!("Initializing indexer...");
     std::io::stdout().flush().ok();
 
+    let http_addr = config.http_addr;
     let indexer = match BlockIndexer::new(config).await {
         Ok(i) => {
             info!("Indexer initialized successfully");
             std::io::stdout().flush().ok();
-            i
+            Arc::new(i)
         }
         Err(e) => {
             eprintln!("ERROR: Failed to initialize indexer: {}", e);
@@ -89,28 +142,97 @@ async fn run() -> Result<()> {
         }
     };
 
+    match command {
+        Command::Run => sync(indexer, http_addr, None, true).await,
+        Command::Backfill { from: None, to } => sync(indexer, http_addr, to, false).await,
+        Command::Backfill {
+            from: Some(from),
+            to,
+        } => indexer.backfill_range(from, to).await,
+        Command::Reindex { from, to } => indexer.reindex(from, to).await,
+        Command::Status => indexer.status().await,
+        Command::Verify {
+            from,
+            to,
+            sample,
+            fix,
+        } => {
+            let options = VerifyOptions {
+                from,
+                to,
+                sample,
+                fix,
+            };
+            indexer.verify(options).await
+        }
+        Command::Repair { dry_run } => indexer.repair_gaps(dry_run).await,
+        Command::ResetCheckpoint { to } => indexer.reset_checkpoint(to).await,
+        Command::InitIndices | Command::Config { .. } => {
+            unreachable!("handled before the indexer is created")
+        }
+    }
+}
+
+/// Backfill up to `to` or the chain head, then follow new blocks if `live` is set, serving
+/// metrics and health checks on `http_addr` meanwhile
+async fn sync(
+    indexer: Arc<BlockIndexer>,
+    http_addr: Option<std::net::SocketAddr>,
+    to: Option<u64>,
+    live: bool,
+) -> Result<()> {
+    use std::io::Write;
+
+    if let Some(addr) = http_addr {
+        let indexer = Arc::clone(&indexer);
+        tokio::spawn(async move {
+            if let Err(e) = server::serve(addr, indexer).await {
+                error!("{:#}", e);
+            }
+        });
+    }
+
+    // SIGINT/SIGTERM stop the sync after in-flight batches drain; a second signal exits at once
+    {
+        let indexer = Arc::clone(&indexer);
+        tokio::spawn(async move {
+            shutdown::signal().await;
+            info!("Shutdown requested, finishing in-flight work...");
+            indexer.shutdown();
+
+            shutdown::signal().await;
+            error!("Received a second shutdown signal, exiting without draining");
+            std::process::exit(1);
+        });
+    }
+
     // Run historical sync first
     info!("Starting historical sync...");
     std::io::stdout().flush().ok();
 
-    if let Err(e) = indexer.sync_historical().await {
+    if let Err(e) = indexer.sync_historical(to).await {
         eprintln!("ERROR: Historical sync failed: {}", e);
         std::io::stderr().flush().ok();
         return Err(e);
     }
 
-    info!("Historical sync completed");
-    std::io::stdout().flush().ok();
+    if live && !indexer.is_shutting_down() {
+        info!("Historical sync completed");
+        std::io::stdout().flush().ok();
 
-    // Then keep syncing live
-    info!("Starting live sync...");
-    std::io::stdout().flush().ok();
+        // Then keep syncing live until shutdown
+        info!("Starting live sync...");
+        std::io::stdout().flush().ok();
 
-    if let Err(e) = indexer.sync_live().await {
-        eprintln!("ERROR: Live sync failed: {}", e);
-        std::io::stderr().flush().ok();
-        return Err(e);
+        if let Err(e) = indexer.sync_live().await {
+            eprintln!("ERROR: Live sync failed: {}", e);
+            std::io::stderr().flush().ok();
+            return Err(e);
+        }
     }
 
+    indexer.log_summary();
+    std::io::stdout().flush().ok();
+
     Ok(())
 }
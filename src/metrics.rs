@@ -0,0 +1,194 @@
+use prometheus::{
+    Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::LazyLock;
+
+use crate::models::IndexedBlock;
+
+/// Process-wide metrics, exposed on `/metrics`
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Buckets for RPC and Elasticsearch request durations, in seconds
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0,
+];
+
+pub struct Metrics {
+    registry: Registry,
+    /// Latest block reported by the RPC nodes
+    pub head_block: IntGauge,
+    /// Checkpoint: every block up to it is indexed
+    pub indexed_block: IntGauge,
+    pub lag_blocks: IntGauge,
+    pub blocks_indexed: IntCounter,
+    pub transactions_indexed: IntCounter,
+    /// Blocks that could not be indexed, by `IndexerError::kind`
+    pub block_failures: IntCounterVec,
+    pub rpc_request_duration: HistogramVec,
+    pub rpc_errors: IntCounterVec,
+    pub rpc_retries: IntCounter,
+    pub rpc_concurrency_limit: IntGauge,
+    pub es_bulk_duration: Histogram,
+    pub es_bulk_item_failures: IntCounter,
+    pub es_bulk_retries: IntCounter,
+}
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(registry: &Registry, metric: T) -> T {
+    registry
+        .register(Box::new(metric.clone()))
+        .expect("metric names are unique");
+    metric
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("indexer".to_string()), None).expect("valid registry prefix");
+
+        let gauge = |name: &str, help: &str| {
+            register(&registry, IntGauge::new(name, help).expect("valid gauge"))
+        };
+        let counter = |name: &str, help: &str| {
+            register(
+                &registry,
+                IntCounter::new(name, help).expect("valid counter"),
+            )
+        };
+        let counter_vec = |name: &str, help: &str, labels: &[&str]| {
+            register(
+                &registry,
+                IntCounterVec::new(Opts::new(name, help), labels).expect("valid counter"),
+            )
+        };
+        let histogram_opts = |name: &str, help: &str| {
+            HistogramOpts::new(name, help).buckets(LATENCY_BUCKETS.to_vec())
+        };
+
+        Metrics {
+            head_block: gauge("head_block", "Latest block reported by the RPC nodes"),
+            indexed_block: gauge(
+                "indexed_block",
+                "Checkpoint block; every block up to it is indexed",
+            ),
+            lag_blocks: gauge(
+                "lag_blocks",
+                "Blocks between the chain head and the checkpoint",
+            ),
+            blocks_indexed: counter("blocks_indexed_total", "Blocks written to the sinks"),
+            transactions_indexed: counter(
+                "transactions_indexed_total",
+                "Transactions written to the sinks",
+            ),
+            block_failures: counter_vec(
+                "block_failures_total",
+                "Blocks that could not be indexed, by error kind",
+                &["kind"],
+            ),
+            rpc_request_duration: register(
+                &registry,
+                HistogramVec::new(
+                    histogram_opts(
+                        "rpc_request_duration_seconds",
+                        "Duration of RPC requests, by JSON-RPC method",
+                    ),
+                    &["method"],
+                )
+                .expect("valid histogram"),
+            ),
+            rpc_errors: counter_vec(
+                "rpc_errors_total",
+                "Failed RPC requests, by JSON-RPC method",
+                &["method"],
+            ),
+            rpc_retries: counter(
+                "rpc_retries_total",
+                "RPC calls retried after a transient error",
+            ),
+            rpc_concurrency_limit: gauge(
+                "rpc_concurrency_limit",
+                "Current adaptive limit of concurrent block fetches",
+            ),
+            es_bulk_duration: register(
+                &registry,
+                Histogram::with_opts(histogram_opts(
+                    "es_bulk_duration_seconds",
+                    "Duration of Elasticsearch bulk requests",
+                ))
+                .expect("valid histogram"),
+            ),
+            es_bulk_item_failures: counter(
+                "es_bulk_item_failures_total",
+                "Bulk items rejected by Elasticsearch, including ones retried later",
+            ),
+            es_bulk_retries: counter(
+                "es_bulk_retries_total",
+                "Bulk requests resent for rejected items",
+            ),
+            registry,
+        }
+    }
+
+    pub fn set_head_block(&self, block_number: u64) {
+        self.head_block.set(block_number as i64);
+        self.update_lag();
+    }
+
+    pub fn set_indexed_block(&self, block_number: u64) {
+        self.indexed_block.set(block_number as i64);
+        self.update_lag();
+    }
+
+    fn update_lag(&self) {
+        let lag = self.head_block.get() - self.indexed_block.get();
+        self.lag_blocks.set(lag.max(0));
+    }
+
+    /// Count blocks and their transactions once they are written
+    pub fn record_indexed(&self, blocks: &[IndexedBlock]) {
+        self.blocks_indexed.inc_by(blocks.len() as u64);
+        self.transactions_indexed
+            .inc_by(blocks.iter().map(|b| b.transaction_count as u64).sum());
+    }
+
+    /// Metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lag_follows_head_and_checkpoint() {
+        let metrics = Metrics::new();
+
+        metrics.set_head_block(1_000);
+        metrics.set_indexed_block(990);
+        assert_eq!(metrics.lag_blocks.get(), 10);
+
+        // The checkpoint can briefly be ahead of a stale head
+        metrics.set_indexed_block(1_005);
+        assert_eq!(metrics.lag_blocks.get(), 0);
+    }
+
+    #[test]
+    fn test_render_uses_prefix() {
+        let metrics = Metrics::new();
+        metrics.set_head_block(42);
+        metrics
+            .rpc_request_duration
+            .with_label_values(&["eth_blockNumber"])
+            .observe(0.02);
+
+        let text = metrics.render();
+        assert!(text.contains("indexer_head_block 42"));
+        assert!(
+            text.contains("indexer_rpc_request_duration_seconds_bucket{method=\"eth_blockNumber\"")
+        );
+    }
+}
@@ -13,10 +13,75 @@ pub struct IndexedBlock {
     pub difficulty: String,
     pub total_difficulty: String,
     pub size: u64,
+    #[serde(default)]
     pub transactions: Vec<IndexedTransaction>,
     pub transaction_count: usize,
     pub uncles: usize,
     pub indexed_at: u64,
+    #[serde(default)]
+    pub finality: FinalityStatus,
+    /// EIP-1559 base fee in wei
+    #[serde(default)]
+    pub base_fee_per_gas: Option<String>,
+    #[serde(default)]
+    pub withdrawals_root: Option<String>,
+    /// EIP-4844 blob gas fields
+    #[serde(default)]
+    pub blob_gas_used: Option<u64>,
+    #[serde(default)]
+    pub excess_blob_gas: Option<u64>,
+    /// Beacon chain withdrawals (EIP-4895)
+    #[serde(default)]
+    pub withdrawals: Vec<IndexedWithdrawal>,
+    /// Event logs emitted in this block, stored in the logs index rather than the block document
+    #[serde(skip)]
+    pub logs: Vec<IndexedLog>,
+}
+
+impl IndexedBlock {
+    /// Transactions of this block as standalone documents for the flat transactions index
+    pub fn flat_transactions(&self) -> impl Iterator<Item = FlatTransaction<'_>> {
+        self.transactions
+            .iter()
+            .map(move |transaction| FlatTransaction {
+                transaction,
+                block_number: self.number,
+                block_hash: &self.hash,
+                timestamp: self.timestamp,
+                miner: self.miner.as_deref(),
+            })
+    }
+}
+
+/// Finality of an indexed block as reported by the node's `safe`/`finalized` tags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalityStatus {
+    #[default]
+    Unfinalized,
+    Safe,
+    Finalized,
+}
+
+impl FinalityStatus {
+    /// Status of `number` given the node's current safe and finalized heads
+    pub fn for_block(number: u64, safe: Option<u64>, finalized: Option<u64>) -> Self {
+        if finalized.is_some_and(|f| number <= f) {
+            FinalityStatus::Finalized
+        } else if safe.is_some_and(|s| number <= s) {
+            FinalityStatus::Safe
+        } else {
+            FinalityStatus::Unfinalized
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FinalityStatus::Unfinalized => "unfinalized",
+            FinalityStatus::Safe => "safe",
+            FinalityStatus::Finalized => "finalized",
+        }
+    }
 }
 
 /// Represents a blockchain transaction within an indexed block
@@ -31,6 +96,154 @@ pub struct IndexedTransaction {
     pub input: String,
     pub nonce: u64,
     pub transaction_index: Option<u64>,
+    /// Receipt status: 1 for success, 0 for failure (absent before Byzantium)
+    #[serde(default)]
+    pub status: Option<u64>,
+    #[serde(default)]
+    pub gas_used: Option<u64>,
+    #[serde(default)]
+    pub cumulative_gas_used: Option<u64>,
+    #[serde(default)]
+    pub effective_gas_price: Option<String>,
+    #[serde(default)]
+    pub contract_address: Option<String>,
+    #[serde(default)]
+    pub logs_bloom: Option<String>,
+    /// EIP-2718 transaction type (0 legacy, 1 access list, 2 dynamic fee, 3 blob)
+    #[serde(default, rename = "type")]
+    pub transaction_type: Option<u64>,
+    #[serde(default)]
+    pub max_fee_per_gas: Option<String>,
+    #[serde(default)]
+    pub max_priority_fee_per_gas: Option<String>,
+    #[serde(default)]
+    pub max_fee_per_blob_gas: Option<String>,
+    #[serde(default)]
+    pub access_list: Option<Vec<IndexedAccessListItem>>,
+    #[serde(default)]
+    pub chain_id: Option<u64>,
+    #[serde(default)]
+    pub blob_versioned_hashes: Option<Vec<String>>,
+}
+
+/// Entry of an EIP-2930 access list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedAccessListItem {
+    pub address: String,
+    pub storage_keys: Vec<String>,
+}
+
+/// Beacon chain withdrawal included in a block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedWithdrawal {
+    pub index: u64,
+    pub validator_index: u64,
+    pub address: String,
+    /// Amount in gwei
+    pub amount: u64,
+}
+
+/// A transaction together with the block fields needed to query it on its own
+#[derive(Debug, Clone, Serialize)]
+pub struct FlatTransaction<'a> {
+    #[serde(flatten)]
+    pub transaction: &'a IndexedTransaction,
+    pub block_number: u64,
+    pub block_hash: &'a str,
+    pub timestamp: u64,
+    pub miner: Option<&'a str>,
+}
+
+/// Represents an event log emitted by a transaction, indexed into the logs index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedLog {
+    pub address: String,
+    pub topic0: Option<String>,
+    pub topic1: Option<String>,
+    pub topic2: Option<String>,
+    pub topic3: Option<String>,
+    pub data: String,
+    pub log_index: u64,
+    pub transaction_hash: String,
+    pub transaction_index: u64,
+    pub block_number: u64,
+    pub block_hash: String,
+    pub timestamp: u64,
+    pub removed: bool,
+}
+
+impl IndexedLog {
+    /// Document id in the logs index; log indices are unique within a block
+    pub fn doc_id(&self) -> String {
+        format!("{}-{}", self.block_number, self.log_index)
+    }
+}
+
+/// A block with one transaction emitting one log, for tests of the storage backends
+#[cfg(test)]
+pub fn test_block(number: u64) -> IndexedBlock {
+    let hash = format!("0x{:064x}", number);
+    let tx_hash = format!("0x{:064x}", number + 1_000_000);
+    IndexedBlock {
+        number,
+        hash: hash.clone(),
+        parent_hash: format!("0x{:064x}", number.saturating_sub(1)),
+        timestamp: 1_700_000_000 + number * 12,
+        gas_limit: 30_000_000,
+        gas_used: 21_000,
+        miner: Some("0x95222290dd7278aa3ddd389cc1e1d165cc4bafe5".to_string()),
+        difficulty: "0".to_string(),
+        total_difficulty: "58750003716598352816469".to_string(),
+        size: 1_000,
+        transactions: vec![IndexedTransaction {
+            hash: tx_hash.clone(),
+            from: "0x1111111111111111111111111111111111111111".to_string(),
+            to: Some("0x2222222222222222222222222222222222222222".to_string()),
+            value: "1000000000000000000".to_string(),
+            gas: 21_000,
+            gas_price: "30000000000".to_string(),
+            input: "0x".to_string(),
+            nonce: number,
+            transaction_index: Some(0),
+            status: Some(1),
+            gas_used: Some(21_000),
+            cumulative_gas_used: Some(21_000),
+            effective_gas_price: Some("30000000000".to_string()),
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: Some(2),
+            max_fee_per_gas: Some("40000000000".to_string()),
+            max_priority_fee_per_gas: Some("1000000000".to_string()),
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: Some(1),
+            blob_versioned_hashes: None,
+        }],
+        transaction_count: 1,
+        uncles: 0,
+        indexed_at: 1_700_000_100,
+        finality: FinalityStatus::Unfinalized,
+        base_fee_per_gas: Some("29000000000".to_string()),
+        withdrawals_root: None,
+        blob_gas_used: None,
+        excess_blob_gas: None,
+        withdrawals: vec![],
+        logs: vec![IndexedLog {
+            address: "0x2222222222222222222222222222222222222222".to_string(),
+            topic0: Some(format!("0x{:064x}", 0xddf2u64)),
+            topic1: None,
+            topic2: None,
+            topic3: None,
+            data: "0x".to_string(),
+            log_index: 0,
+            transaction_hash: tx_hash,
+            transaction_index: 0,
+            block_number: number,
+            block_hash: hash,
+            timestamp: 1_700_000_000 + number * 12,
+            removed: false,
+        }],
+    }
 }
 
 #[cfg(test)]
@@ -54,6 +267,13 @@ mod tests {
             transaction_count: 0,
             uncles: 0,
             indexed_at: 1234567890,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
         };
 
         let json = serde_json::to_string(&block).unwrap();
@@ -75,6 +295,19 @@ mod tests {
             input: "0x".to_string(),
             nonce: 0,
             transaction_index: Some(0),
+            status: None,
+            gas_used: None,
+            cumulative_gas_used: None,
+            effective_gas_price: None,
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: None,
+            blob_versioned_hashes: None,
         };
 
         let json = serde_json::to_string(&tx).unwrap();
@@ -97,6 +330,19 @@ mod tests {
                 input: "0x".to_string(),
                 nonce: 0,
                 transaction_index: Some(0),
+                status: None,
+                gas_used: None,
+                cumulative_gas_used: None,
+                effective_gas_price: None,
+                contract_address: None,
+                logs_bloom: None,
+                transaction_type: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                max_fee_per_blob_gas: None,
+                access_list: None,
+                chain_id: None,
+                blob_versioned_hashes: None,
             },
             IndexedTransaction {
                 hash: "0x222".to_string(),
@@ -108,6 +354,19 @@ mod tests {
                 input: "0x1234".to_string(),
                 nonce: 1,
                 transaction_index: Some(1),
+                status: None,
+                gas_used: None,
+                cumulative_gas_used: None,
+                effective_gas_price: None,
+                contract_address: None,
+                logs_bloom: None,
+                transaction_type: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                max_fee_per_blob_gas: None,
+                access_list: None,
+                chain_id: None,
+                blob_versioned_hashes: None,
             },
         ];
 
@@ -126,6 +385,13 @@ mod tests {
             transaction_count: 2,
             uncles: 0,
             indexed_at: 1234567890,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
         };
 
         assert_eq!(block.transaction_count, block.transactions.len());
@@ -146,6 +412,19 @@ mod tests {
             input: "0x6080604052".to_string(),
             nonce: 5,
             transaction_index: Some(10),
+            status: None,
+            gas_used: None,
+            cumulative_gas_used: None,
+            effective_gas_price: None,
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: None,
+            blob_versioned_hashes: None,
         };
 
         let json = serde_json::to_string(&tx).unwrap();
@@ -172,6 +451,13 @@ mod tests {
             transaction_count: 0,
             uncles: 0,
             indexed_at: 0,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
         };
 
         let json = serde_json::to_string(&block).unwrap();
@@ -193,6 +479,19 @@ mod tests {
             input: "0x".to_string(),
             nonce: 0,
             transaction_index: None,
+            status: None,
+            gas_used: None,
+            cumulative_gas_used: None,
+            effective_gas_price: None,
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: None,
+            blob_versioned_hashes: None,
         };
 
         let json = serde_json::to_string(&tx).unwrap();
@@ -222,6 +521,13 @@ mod tests {
             transaction_count: 0,
             uncles: u64::MAX as usize,
             indexed_at: u64::MAX,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
         };
 
         let json = serde_json::to_string(&block).unwrap();
@@ -247,6 +553,19 @@ mod tests {
             input: "0x".to_string().repeat(1000), // Very long input
             nonce: u64::MAX,
             transaction_index: Some(u64::MAX),
+            status: None,
+            gas_used: None,
+            cumulative_gas_used: None,
+            effective_gas_price: None,
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: None,
+            blob_versioned_hashes: None,
         };
 
         let json = serde_json::to_string(&tx).unwrap();
@@ -281,10 +600,30 @@ mod tests {
                 input: "0x123456".to_string(),
                 nonce: 5,
                 transaction_index: Some(0),
+                status: None,
+                gas_used: None,
+                cumulative_gas_used: None,
+                effective_gas_price: None,
+                contract_address: None,
+                logs_bloom: None,
+                transaction_type: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                max_fee_per_blob_gas: None,
+                access_list: None,
+                chain_id: None,
+                blob_versioned_hashes: None,
             }],
             transaction_count: 1,
             uncles: 2,
             indexed_at: 1609459200,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
         };
 
         // Serialize
@@ -322,6 +661,19 @@ mod tests {
             input: "".to_string(),
             nonce: 0,
             transaction_index: Some(0),
+            status: None,
+            gas_used: None,
+            cumulative_gas_used: None,
+            effective_gas_price: None,
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: None,
+            blob_versioned_hashes: None,
         };
 
         let json = serde_json::to_string(&tx).unwrap();
@@ -345,6 +697,19 @@ mod tests {
                 input: format!("0x{:02x}", i % 256),
                 nonce: i,
                 transaction_index: Some(i),
+                status: None,
+                gas_used: None,
+                cumulative_gas_used: None,
+                effective_gas_price: None,
+                contract_address: None,
+                logs_bloom: None,
+                transaction_type: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                max_fee_per_blob_gas: None,
+                access_list: None,
+                chain_id: None,
+                blob_versioned_hashes: None,
             })
             .collect();
 
@@ -363,6 +728,13 @@ mod tests {
             transaction_count: 100,
             uncles: 0,
             indexed_at: 1234567890,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
         };
 
         assert_eq!(block.transactions.len(), 100);
@@ -376,4 +748,293 @@ mod tests {
             "0x0000000000000000000000000000000000000000000000000000000000000063"
         );
     }
+
+    #[test]
+    fn test_finality_status_for_block() {
+        assert_eq!(
+            FinalityStatus::for_block(100, Some(120), Some(110)),
+            FinalityStatus::Finalized
+        );
+        assert_eq!(
+            FinalityStatus::for_block(115, Some(120), Some(110)),
+            FinalityStatus::Safe
+        );
+        assert_eq!(
+            FinalityStatus::for_block(121, Some(120), Some(110)),
+            FinalityStatus::Unfinalized
+        );
+        assert_eq!(
+            FinalityStatus::for_block(0, None, None),
+            FinalityStatus::Unfinalized
+        );
+    }
+
+    #[test]
+    fn test_indexed_block_finality_defaults_when_missing() {
+        let json = r#"{"number":1,"hash":"0x1","parent_hash":"0x0","timestamp":0,"gas_limit":0,"gas_used":0,"miner":null,"difficulty":"0","total_difficulty":"0","size":0,"transactions":[],"transaction_count":0,"uncles":0,"indexed_at":0}"#;
+        let block: IndexedBlock = serde_json::from_str(json).unwrap();
+        assert_eq!(block.finality, FinalityStatus::Unfinalized);
+
+        let json = serde_json::to_string(&FinalityStatus::Finalized).unwrap();
+        assert_eq!(json, "\"finalized\"");
+    }
+
+    #[test]
+    fn test_indexed_transaction_with_receipt_fields() {
+        let tx = IndexedTransaction {
+            hash: "0xfailed".to_string(),
+            from: "0xfrom".to_string(),
+            to: None,
+            value: "0".to_string(),
+            gas: 100000,
+            gas_price: "20".to_string(),
+            input: "0x".to_string(),
+            nonce: 3,
+            transaction_index: Some(0),
+            status: Some(0),
+            gas_used: Some(64000),
+            cumulative_gas_used: Some(64000),
+            effective_gas_price: Some("20".to_string()),
+            contract_address: Some("0xcontract".to_string()),
+            logs_bloom: Some("0x00".to_string()),
+            transaction_type: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            access_list: None,
+            chain_id: None,
+            blob_versioned_hashes: None,
+        };
+
+        let json = serde_json::to_string(&tx).unwrap();
+        let deserialized: IndexedTransaction = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized.status, Some(0));
+        assert_eq!(deserialized.gas_used, Some(64000));
+        assert_eq!(deserialized.effective_gas_price, Some("20".to_string()));
+        assert_eq!(
+            deserialized.contract_address,
+            Some("0xcontract".to_string())
+        );
+    }
+
+    #[test]
+    fn test_indexed_transaction_without_receipt_fields() {
+        let json = r#"{"hash":"0x1","from":"0x2","to":null,"value":"0","gas":21000,"gas_price":"1","input":"","nonce":0,"transaction_index":0}"#;
+        let tx: IndexedTransaction = serde_json::from_str(json).unwrap();
+
+        assert_eq!(tx.status, None);
+        assert_eq!(tx.gas_used, None);
+        assert_eq!(tx.logs_bloom, None);
+    }
+
+    #[test]
+    fn test_indexed_log_serialization() {
+        let log = IndexedLog {
+            address: "0xtoken".to_string(),
+            topic0: Some("0xddf252ad".to_string()),
+            topic1: Some("0xfrom".to_string()),
+            topic2: Some("0xto".to_string()),
+            topic3: None,
+            data: "00ff".to_string(),
+            log_index: 7,
+            transaction_hash: "0xtx".to_string(),
+            transaction_index: 2,
+            block_number: 100,
+            block_hash: "0xblock".to_string(),
+            timestamp: 1234567890,
+            removed: false,
+        };
+
+        let json = serde_json::to_string(&log).unwrap();
+        let deserialized: IndexedLog = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(log.topic0, deserialized.topic0);
+        assert_eq!(deserialized.topic3, None);
+        assert_eq!(deserialized.doc_id(), "100-7");
+    }
+
+    #[test]
+    fn test_indexed_block_logs_are_not_serialized() {
+        let block = IndexedBlock {
+            number: 5,
+            hash: "0xblock".to_string(),
+            parent_hash: "0xparent".to_string(),
+            timestamp: 0,
+            gas_limit: 0,
+            gas_used: 0,
+            miner: None,
+            difficulty: "0".to_string(),
+            total_difficulty: "0".to_string(),
+            size: 0,
+            transactions: vec![],
+            transaction_count: 0,
+            uncles: 0,
+            indexed_at: 0,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![IndexedLog {
+                address: "0xtoken".to_string(),
+                topic0: None,
+                topic1: None,
+                topic2: None,
+                topic3: None,
+                data: String::new(),
+                log_index: 0,
+                transaction_hash: "0xtx".to_string(),
+                transaction_index: 0,
+                block_number: 5,
+                block_hash: "0xblock".to_string(),
+                timestamp: 0,
+                removed: false,
+            }],
+        };
+
+        let json = serde_json::to_value(&block).unwrap();
+        assert!(json.get("logs").is_none());
+    }
+
+    #[test]
+    fn test_flat_transactions_carry_block_fields() {
+        let block = IndexedBlock {
+            number: 42,
+            hash: "0xblock".to_string(),
+            parent_hash: "0xparent".to_string(),
+            timestamp: 1700000000,
+            gas_limit: 30000000,
+            gas_used: 21000,
+            miner: Some("0xminer".to_string()),
+            difficulty: "0".to_string(),
+            total_difficulty: "0".to_string(),
+            size: 600,
+            transactions: vec![IndexedTransaction {
+                hash: "0xtx".to_string(),
+                from: "0xfrom".to_string(),
+                to: Some("0xto".to_string()),
+                value: "1".to_string(),
+                gas: 21000,
+                gas_price: "1".to_string(),
+                input: "".to_string(),
+                nonce: 0,
+                transaction_index: Some(0),
+                status: Some(1),
+                gas_used: Some(21000),
+                cumulative_gas_used: Some(21000),
+                effective_gas_price: Some("1".to_string()),
+                contract_address: None,
+                logs_bloom: None,
+                transaction_type: None,
+                max_fee_per_gas: None,
+                max_priority_fee_per_gas: None,
+                max_fee_per_blob_gas: None,
+                access_list: None,
+                chain_id: None,
+                blob_versioned_hashes: None,
+            }],
+            transaction_count: 1,
+            uncles: 0,
+            indexed_at: 0,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
+        };
+
+        let flat: Vec<_> = block.flat_transactions().collect();
+        assert_eq!(flat.len(), 1);
+
+        let json = serde_json::to_value(&flat[0]).unwrap();
+        assert_eq!(json["hash"], "0xtx");
+        assert_eq!(json["block_number"], 42);
+        assert_eq!(json["block_hash"], "0xblock");
+        assert_eq!(json["timestamp"], 1700000000u64);
+        assert_eq!(json["miner"], "0xminer");
+    }
+
+    #[test]
+    fn test_indexed_transaction_type_field_name() {
+        let tx = IndexedTransaction {
+            hash: "0xblob".to_string(),
+            from: "0xfrom".to_string(),
+            to: Some("0xto".to_string()),
+            value: "0".to_string(),
+            gas: 21000,
+            gas_price: "30".to_string(),
+            input: "".to_string(),
+            nonce: 1,
+            transaction_index: Some(0),
+            status: Some(1),
+            gas_used: Some(21000),
+            cumulative_gas_used: Some(21000),
+            effective_gas_price: Some("30".to_string()),
+            contract_address: None,
+            logs_bloom: None,
+            transaction_type: Some(3),
+            max_fee_per_gas: Some("50".to_string()),
+            max_priority_fee_per_gas: Some("2".to_string()),
+            max_fee_per_blob_gas: Some("10".to_string()),
+            access_list: Some(vec![IndexedAccessListItem {
+                address: "0xcontract".to_string(),
+                storage_keys: vec!["0x01".to_string()],
+            }]),
+            chain_id: Some(1),
+            blob_versioned_hashes: Some(vec!["0x01abc".to_string()]),
+        };
+
+        let json = serde_json::to_value(&tx).unwrap();
+        assert_eq!(json["type"], 3);
+        assert!(json.get("transaction_type").is_none());
+
+        let deserialized: IndexedTransaction = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized.transaction_type, Some(3));
+        assert_eq!(deserialized.access_list.unwrap()[0].storage_keys.len(), 1);
+        assert_eq!(deserialized.blob_versioned_hashes.unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_indexed_block_with_withdrawals() {
+        let block = IndexedBlock {
+            number: 17034870,
+            hash: "0xshanghai".to_string(),
+            parent_hash: "0xparent".to_string(),
+            timestamp: 1681338479,
+            gas_limit: 30000000,
+            gas_used: 0,
+            miner: None,
+            difficulty: "0".to_string(),
+            total_difficulty: "0".to_string(),
+            size: 0,
+            transactions: vec![],
+            transaction_count: 0,
+            uncles: 0,
+            indexed_at: 0,
+            finality: FinalityStatus::Finalized,
+            base_fee_per_gas: Some("7000000000".to_string()),
+            withdrawals_root: Some("0xroot".to_string()),
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![IndexedWithdrawal {
+                index: 0,
+                validator_index: 12345,
+                address: "0xvalidator".to_string(),
+                amount: 32000000000,
+            }],
+            logs: vec![],
+        };
+
+        let json = serde_json::to_string(&block).unwrap();
+        let deserialized: IndexedBlock = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized.base_fee_per_gas, block.base_fee_per_gas);
+        assert_eq!(deserialized.withdrawals.len(), 1);
+        assert_eq!(deserialized.withdrawals[0].validator_index, 12345);
+        assert_eq!(deserialized.withdrawals[0].amount, 32000000000);
+    }
 }
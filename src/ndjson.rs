@@ -0,0 +1,423 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{Compression, Config, NdjsonDocuments};
+use crate::models::{FinalityStatus, IndexedBlock};
+use crate::progress::SyncProgress;
+use crate::sink::Sink;
+
+/// Streams indexed blocks as newline-delimited JSON, one document per block or per transaction,
+/// to stdout or to rotating files in a directory.
+///
+/// The output is append-only: rollbacks and finality promotions are not written, and a block
+/// indexed again is simply written again, so consumers keep the latest copy of each block.
+/// The most recent blocks are kept in memory so that live sync can detect reorgs.
+pub struct NdjsonSink {
+    output: Arc<Mutex<Output>>,
+    directory: Option<PathBuf>,
+    checkpoint_file: Option<PathBuf>,
+    /// Progress when there is no checkpoint file, lost on exit
+    progress: Mutex<SyncProgress>,
+    recent: Mutex<BTreeMap<u64, IndexedBlock>>,
+    recent_limit: usize,
+}
+
+/// Write `lines` as they are, or as one complete gzip member or zstd frame. Concatenated
+/// members and frames decode as a single stream, and everything written before a crash stays
+/// readable since no frame is left open between writes.
+fn write_lines(out: &mut dyn Write, compression: Compression, lines: &[u8]) -> io::Result<()> {
+    match compression {
+        Compression::None => out.write_all(lines),
+        Compression::Gzip => {
+            let mut encoder = GzEncoder::new(out, flate2::Compression::default());
+            encoder.write_all(lines)?;
+            encoder.finish()?;
+            Ok(())
+        }
+        Compression::Zstd => {
+            let mut encoder = zstd::Encoder::new(out, 0)?;
+            encoder.write_all(lines)?;
+            encoder.finish()?;
+            Ok(())
+        }
+    }
+}
+
+struct Output {
+    /// Directory of the rotating files; stdout when `None`
+    directory: Option<PathBuf>,
+    documents: NdjsonDocuments,
+    compression: Compression,
+    max_file_bytes: u64,
+    /// Distinguishes the files of this process from those of earlier runs
+    run_id: u128,
+    file: Option<BufWriter<Box<dyn Write + Send>>>,
+    /// Uncompressed bytes written to the current file
+    file_bytes: u64,
+}
+
+impl Output {
+    fn new(config: &Config) -> Self {
+        Output {
+            directory: config.ndjson_output.clone(),
+            documents: config.ndjson_documents,
+            compression: config.ndjson_compression,
+            max_file_bytes: config.ndjson_max_file_mb * 1024 * 1024,
+            run_id: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis()),
+            file: None,
+            file_bytes: 0,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self.compression {
+            Compression::None => "ndjson",
+            Compression::Gzip => "ndjson.gz",
+            Compression::Zstd => "ndjson.zst",
+        }
+    }
+
+    /// The open stream, starting a file named after `first_block` and the run if none is open.
+    /// A previous run may have been killed in the middle of a write, so its files are never
+    /// appended to.
+    fn file(&mut self, first_block: u64) -> Result<&mut BufWriter<Box<dyn Write + Send>>> {
+        if self.file.is_none() {
+            let inner: Box<dyn Write + Send> = match &self.directory {
+                None => Box::new(io::stdout()),
+                Some(directory) => {
+                    let path = directory.join(format!(
+                        "{}-{:012}-{}.{}",
+                        self.documents.as_str(),
+                        first_block,
+                        self.run_id,
+                        self.extension()
+                    ));
+                    // Appending only happens within this run, after complete writes
+                    let file = OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(&path)
+                        .with_context(|| format!("Failed to open {}", path.display()))?;
+                    log::debug!("Writing to {}", path.display());
+                    Box::new(file)
+                }
+            };
+            self.file = Some(BufWriter::new(inner));
+        }
+        Ok(self.file.as_mut().expect("file was just opened"))
+    }
+
+    /// Write and flush the documents of `blocks`, then rotate the file if it is full
+    fn write(&mut self, blocks: &[IndexedBlock]) -> Result<()> {
+        let mut lines = Vec::new();
+        for block in blocks {
+            match self.documents {
+                NdjsonDocuments::Blocks => {
+                    serde_json::to_writer(&mut lines, block)?;
+                    lines.push(b'\n');
+                }
+                NdjsonDocuments::Transactions => {
+                    for transaction in block.flat_transactions() {
+                        serde_json::to_writer(&mut lines, &transaction)?;
+                        lines.push(b'\n');
+                    }
+                }
+            }
+        }
+        let Some(first) = blocks.first() else {
+            return Ok(());
+        };
+        if lines.is_empty() {
+            return Ok(());
+        }
+
+        let compression = self.compression;
+        let file = self.file(first.number)?;
+        write_lines(file, compression, &lines)?;
+        file.flush()?;
+        self.file_bytes += lines.len() as u64;
+
+        if self.directory.is_some() && self.file_bytes >= self.max_file_bytes {
+            self.file = None;
+            self.file_bytes = 0;
+        }
+        Ok(())
+    }
+}
+
+impl NdjsonSink {
+    pub fn open(config: &Config) -> Result<Self> {
+        if let Some(directory) = &config.ndjson_output {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("Failed to create {}", directory.display()))?;
+        }
+        if config.ndjson_checkpoint_file.is_none() {
+            log::warn!("No NDJSON_CHECKPOINT_FILE, the sync progress is not kept across restarts");
+        }
+
+        Ok(NdjsonSink {
+            output: Arc::new(Mutex::new(Output::new(config))),
+            directory: config.ndjson_output.clone(),
+            checkpoint_file: config.ndjson_checkpoint_file.clone(),
+            progress: Mutex::new(SyncProgress::default()),
+            recent: Mutex::new(BTreeMap::new()),
+            recent_limit: config.max_reorg_depth as usize + 1,
+        })
+    }
+
+    fn remember(&self, blocks: &[IndexedBlock]) {
+        let mut recent = self.recent.lock().expect("recent blocks poisoned");
+        for block in blocks {
+            recent.insert(block.number, block.clone());
+        }
+        while recent.len() > self.recent_limit {
+            recent.pop_first();
+        }
+    }
+}
+
+fn save_checkpoint(path: &Path, progress: &SyncProgress) -> Result<()> {
+    // Replace the file atomically so a crash never leaves a truncated checkpoint
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, serde_json::to_vec(progress)?)
+        .with_context(|| format!("Failed to write {}", temporary.display()))?;
+    fs::rename(&temporary, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+#[async_trait]
+impl Sink for NdjsonSink {
+    fn name(&self) -> String {
+        match &self.directory {
+            Some(directory) => format!("NDJSON ({})", directory.display()),
+            None => "NDJSON (stdout)".to_string(),
+        }
+    }
+
+    async fn create_indices(&self) -> Result<()> {
+        // Files are created as documents are written
+        Ok(())
+    }
+
+    fn keeps_history(&self) -> bool {
+        false
+    }
+
+    async fn ping(&self) -> bool {
+        self.directory
+            .as_ref()
+            .map_or(true, |directory| directory.is_dir())
+    }
+
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        let output = Arc::clone(&self.output);
+        let documents = blocks.to_vec();
+        tokio::task::spawn_blocking(move || {
+            output
+                .lock()
+                .expect("ndjson output poisoned")
+                .write(&documents)
+        })
+        .await
+        .context("ndjson writer panicked")??;
+
+        self.remember(blocks);
+        Ok(())
+    }
+
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        // Written documents stay; the blocks that replace them follow in the output
+        let mut recent = self.recent.lock().expect("recent blocks poisoned");
+        Ok(recent.split_off(&(block_number + 1)).len() as u64)
+    }
+
+    async fn update_finality(&self, _block_number: u64, _status: FinalityStatus) -> Result<u64> {
+        // Documents carry the finality their block had when it was written
+        Ok(0)
+    }
+
+    async fn get_progress(&self) -> Result<SyncProgress> {
+        let Some(path) = &self.checkpoint_file else {
+            return Ok(self.progress.lock().expect("progress poisoned").clone());
+        };
+        match fs::read(path) {
+            Ok(data) => {
+                serde_json::from_slice(&data).with_context(|| format!("Invalid {}", path.display()))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(SyncProgress::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
+        // Documents are written as complete frames and flushed before this is called, so the
+        // checkpoint never runs ahead of readable output
+        match &self.checkpoint_file {
+            Some(path) => save_checkpoint(path, progress),
+            None => {
+                *self.progress.lock().expect("progress poisoned") = progress.clone();
+                Ok(())
+            }
+        }
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        let recent = self.recent.lock().expect("recent blocks poisoned");
+        Ok(recent.get(&block_number).cloned())
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        let recent = self.recent.lock().expect("recent blocks poisoned");
+        Ok(block_numbers
+            .iter()
+            .filter_map(|n| recent.get(n).map(|block| (*n, block.clone())))
+            .collect())
+    }
+
+    async fn block_count_histogram(
+        &self,
+        _from: u64,
+        _to: u64,
+        _interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        anyhow::bail!("The ndjson sink keeps no history to search for gaps")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+    use std::io::Read;
+
+    /// The output files in `dir`, in the order they were started
+    fn output_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_str().unwrap().contains(".ndjson"))
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn read_lines(path: &Path) -> Vec<serde_json::Value> {
+        let file = fs::File::open(path).unwrap();
+        let mut text = String::new();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => flate2::read::MultiGzDecoder::new(file)
+                .read_to_string(&mut text)
+                .unwrap(),
+            Some("zst") => zstd::Decoder::new(file)
+                .unwrap()
+                .read_to_string(&mut text)
+                .unwrap(),
+            _ => io::BufReader::new(file).read_to_string(&mut text).unwrap(),
+        };
+        text.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_rotating_compressed_files() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut output = Output {
+                directory: Some(dir.path().to_path_buf()),
+                documents: NdjsonDocuments::Blocks,
+                compression,
+                max_file_bytes: 1,
+                run_id: 7,
+                file: None,
+                file_bytes: 0,
+            };
+            output.write(&[test_block(1), test_block(2)]).unwrap();
+            output.write(&[test_block(3)]).unwrap();
+
+            let files = output_files(dir.path());
+            let extension = output.extension();
+            assert_eq!(
+                files[0].file_name().unwrap().to_str().unwrap(),
+                format!("blocks-000000000001-7.{}", extension)
+            );
+            let first = read_lines(&files[0]);
+            let second = read_lines(&files[1]);
+            assert_eq!(first.len(), 2);
+            assert_eq!(first[1], serde_json::to_value(test_block(2)).unwrap());
+            assert_eq!(second[0]["number"], 3);
+        }
+    }
+
+    #[test]
+    fn test_killed_process_leaves_readable_files() {
+        for compression in [Compression::Gzip, Compression::Zstd] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut output = Output {
+                directory: Some(dir.path().to_path_buf()),
+                documents: NdjsonDocuments::Blocks,
+                compression,
+                max_file_bytes: u64::MAX,
+                run_id: 7,
+                file: None,
+                file_bytes: 0,
+            };
+            output.write(&[test_block(1), test_block(2)]).unwrap();
+            output.write(&[test_block(3)]).unwrap();
+            // No drop: nothing gets finished on the way out
+            std::mem::forget(output);
+
+            let files = output_files(dir.path());
+            assert_eq!(files.len(), 1);
+            assert_eq!(read_lines(&files[0]).len(), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_documents_and_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::for_test(&[
+            ("SINKS", "ndjson"),
+            ("NDJSON_OUTPUT", dir.path().to_str().unwrap()),
+            ("NDJSON_DOCUMENTS", "transactions"),
+            ("MAX_REORG_DEPTH", "2"),
+        ]);
+        assert!(!config.stdout_is_output());
+
+        let sink = NdjsonSink::open(&config).unwrap();
+        let blocks: Vec<IndexedBlock> = (10..15).map(test_block).collect();
+        sink.write_blocks_with_progress(&blocks, &SyncProgress::from_checkpoint(14))
+            .await
+            .unwrap();
+
+        // Only the last MAX_REORG_DEPTH + 1 blocks can be read back
+        assert!(sink.get_block(11).await.unwrap().is_none());
+        assert_eq!(sink.get_block(14).await.unwrap().unwrap().number, 14);
+        assert_eq!(sink.delete_blocks_after(13).await.unwrap(), 1);
+        assert!(sink.block_count_histogram(0, 20, 10).await.is_err());
+        drop(sink);
+
+        let files = output_files(dir.path());
+        assert_eq!(files.len(), 1);
+        let lines = read_lines(&files[0]);
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0]["block_number"], 10);
+        assert_eq!(lines[0]["hash"], test_block(10).transactions[0].hash);
+
+        let sink = NdjsonSink::open(&config).unwrap();
+        assert_eq!(
+            sink.get_progress().await.unwrap(),
+            SyncProgress::from_checkpoint(14)
+        );
+    }
+}
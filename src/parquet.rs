@@ -0,0 +1,685 @@
+use anyhow::{Context, Result};
+use arrow::array::{Array, ArrayRef, BooleanArray, Decimal256Array, Int64Array, StringArray};
+use arrow::datatypes::{i256, DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use arrow::util::display::array_value_to_string;
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::TryStreamExt;
+#[cfg(feature = "aws")]
+use object_store::aws::AmazonS3Builder;
+#[cfg(feature = "azure")]
+use object_store::azure::MicrosoftAzureBuilder;
+#[cfg(feature = "gcp")]
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::local::LocalFileSystem;
+use object_store::path::Path;
+use object_store::{ObjectStore, PutPayload};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::{ArrowWriter, ProjectionMask};
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use url::Url;
+
+use crate::config::Config;
+use crate::gaps;
+use crate::models::{FinalityStatus, IndexedBlock};
+use crate::progress::SyncProgress;
+use crate::sink::Sink;
+use crate::sql::{self, ColumnType, Row, Table};
+
+/// Precision of wei amounts, the widest Arrow decimal. Amounts are uint256, so chains with a
+/// large native supply exceed the 10^38 of Decimal(38, 0); Decimal(76, 0) holds any amount
+/// below 10^76 wei.
+const DECIMAL_PRECISION: u8 = 76;
+
+const PART_FILE: &str = "part.parquet";
+
+/// Underscore-prefixed files are ignored by Spark, DuckDB and friends when reading a directory
+const PROGRESS_FILE: &str = "_progress.json";
+
+/// Writes blocks, transactions and logs as Parquet files partitioned by block range, e.g.
+/// `blocks/range=0-9999/part.parquet`, to a local directory or an object store.
+///
+/// Blocks are buffered until the checkpoint passes the end of their range, then the range is
+/// written as one file per table, blocks last so that the blocks file marks a finished range.
+/// Every file is committed atomically, and the stored checkpoint never passes a range that is
+/// still buffered, so after a restart the lost buffer is simply indexed again.
+pub struct ParquetSink {
+    store: Arc<dyn ObjectStore>,
+    root: Path,
+    range_size: u64,
+    index_logs: bool,
+    state: Mutex<State>,
+}
+
+struct State {
+    /// Blocks of ranges that are not written yet, by range start
+    pending: BTreeMap<u64, BTreeMap<u64, IndexedBlock>>,
+    /// Start of every range with a blocks file
+    written: BTreeSet<u64>,
+}
+
+/// Open the object store behind `location`: an `s3://`, `gs://` or `az://` URL, with
+/// credentials from the environment variables of each SDK, or else a local directory. Each
+/// cloud store is only available when built with its cargo feature.
+#[cfg_attr(
+    not(any(feature = "aws", feature = "gcp", feature = "azure")),
+    allow(unreachable_code, unused_variables)
+)]
+fn open_store(location: &str) -> Result<(Arc<dyn ObjectStore>, Path)> {
+    let url = match Url::parse(location) {
+        Ok(url) if url.scheme() != "file" => url,
+        _ => {
+            let dir = location.strip_prefix("file://").unwrap_or(location);
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create PARQUET_PATH {}", dir))?;
+            return Ok((
+                Arc::new(LocalFileSystem::new_with_prefix(dir)?),
+                Path::default(),
+            ));
+        }
+    };
+
+    let store: Arc<dyn ObjectStore> = match url.scheme() {
+        #[cfg(feature = "aws")]
+        "s3" | "s3a" => Arc::new(AmazonS3Builder::from_env().with_url(url.as_str()).build()?),
+        #[cfg(feature = "gcp")]
+        "gs" => Arc::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_url(url.as_str())
+                .build()?,
+        ),
+        #[cfg(feature = "azure")]
+        "az" | "abfs" | "abfss" => Arc::new(
+            MicrosoftAzureBuilder::from_env()
+                .with_url(url.as_str())
+                .build()?,
+        ),
+        scheme => anyhow::bail!(
+            "Unsupported PARQUET_PATH scheme {}: expected s3, gs, az or a local directory, \
+             for a build with the aws, gcp or azure feature",
+            scheme
+        ),
+    };
+    Ok((store, Path::from_url_path(url.path())?))
+}
+
+fn arrow_type(kind: ColumnType) -> DataType {
+    match kind {
+        ColumnType::Integer => DataType::Int64,
+        ColumnType::Numeric => DataType::Decimal256(DECIMAL_PRECISION, 0),
+        ColumnType::Text | ColumnType::Json => DataType::Utf8,
+        ColumnType::Boolean => DataType::Boolean,
+    }
+}
+
+fn to_batch(table: &Table, rows: &[Row]) -> Result<RecordBatch> {
+    let fields: Vec<Field> = table
+        .columns
+        .iter()
+        .map(|column| Field::new(column.name, arrow_type(column.kind), true))
+        .collect();
+
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(table.columns.len());
+    for (i, column) in table.columns.iter().enumerate() {
+        let values = rows.iter().map(|row| row[i].as_deref());
+        let context = || format!("Invalid {}.{}", table.suffix, column.name);
+        let array: ArrayRef = match column.kind {
+            ColumnType::Integer => Arc::new(
+                values
+                    .map(|v| v.map(str::parse::<i64>).transpose())
+                    .collect::<Result<Int64Array, _>>()
+                    .with_context(context)?,
+            ),
+            ColumnType::Numeric => {
+                let array = values
+                    .map(|v| {
+                        v.map(|v| {
+                            i256::from_string(v).with_context(|| format!("{} is not an integer", v))
+                        })
+                        .transpose()
+                    })
+                    .collect::<Result<Decimal256Array>>()
+                    .with_context(context)?
+                    .with_precision_and_scale(DECIMAL_PRECISION, 0)?;
+                array
+                    .validate_decimal_precision(DECIMAL_PRECISION)
+                    .with_context(context)?;
+                Arc::new(array)
+            }
+            ColumnType::Boolean => Arc::new(
+                values
+                    .map(|v| v.map(|v| v == "true"))
+                    .collect::<BooleanArray>(),
+            ),
+            ColumnType::Text | ColumnType::Json => Arc::new(values.collect::<StringArray>()),
+        };
+        columns.push(array);
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+fn from_batch(table: &Table, batch: &RecordBatch) -> Result<Vec<Row>> {
+    let mut rows: Vec<Row> = vec![Vec::with_capacity(table.columns.len()); batch.num_rows()];
+    for column in table.columns {
+        let array = batch
+            .column_by_name(column.name)
+            .with_context(|| format!("Missing column {}.{}", table.suffix, column.name))?;
+        for (i, row) in rows.iter_mut().enumerate() {
+            let value = if array.is_null(i) {
+                None
+            } else {
+                Some(array_value_to_string(array, i)?)
+            };
+            row.push(value);
+        }
+    }
+    Ok(rows)
+}
+
+fn encode(table: &Table, rows: &[Row]) -> Result<Vec<u8>> {
+    let batch = to_batch(table, rows)?;
+    let properties = WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::default()))
+        .build();
+    let mut writer = ArrowWriter::try_new(Vec::new(), batch.schema(), Some(properties))?;
+    writer.write(&batch)?;
+    Ok(writer.into_inner()?)
+}
+
+fn decode(table: &Table, data: Bytes) -> Result<Vec<Row>> {
+    let mut rows = Vec::new();
+    for batch in ParquetRecordBatchReaderBuilder::try_new(data)?.build()? {
+        rows.extend(from_batch(table, &batch?)?);
+    }
+    Ok(rows)
+}
+
+/// Read only the block numbers of a blocks file
+fn decode_numbers(data: Bytes) -> Result<Vec<u64>> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(data)?;
+    let mask = ProjectionMask::roots(builder.parquet_schema(), [0]);
+
+    let mut numbers = Vec::new();
+    for batch in builder.with_projection(mask).build()? {
+        let batch = batch?;
+        let column = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .context("blocks.number is not an Int64 column")?;
+        numbers.extend(column.iter().flatten().map(|n| n as u64));
+    }
+    Ok(numbers)
+}
+
+impl ParquetSink {
+    /// Open the store and find the ranges written by previous runs
+    pub async fn open(config: &Config) -> Result<Self> {
+        let location = config
+            .parquet_path
+            .as_deref()
+            .context("PARQUET_PATH is required by the parquet sink")?;
+        let (store, root) = open_store(location)?;
+
+        let files: Vec<_> = store
+            .list(Some(&root.child(sql::BLOCKS.suffix)))
+            .try_collect()
+            .await?;
+        let mut written = BTreeSet::new();
+        for meta in files {
+            if meta.location.filename() != Some(PART_FILE) {
+                continue;
+            }
+            let start = meta.location.parts().find_map(|part| {
+                let range = part.as_ref().strip_prefix("range=")?;
+                range.split('-').next()?.parse::<u64>().ok()
+            });
+            written.extend(start);
+        }
+        log::debug!("Found {} Parquet range(s) in {}", written.len(), location);
+
+        Ok(ParquetSink {
+            store,
+            root,
+            range_size: config.parquet_range_size,
+            index_logs: config.index_receipts && config.index_logs,
+            state: Mutex::new(State {
+                pending: BTreeMap::new(),
+                written,
+            }),
+        })
+    }
+
+    fn range_start(&self, block_number: u64) -> u64 {
+        block_number - block_number % self.range_size
+    }
+
+    fn range_end(&self, start: u64) -> u64 {
+        start + self.range_size - 1
+    }
+
+    fn file_path(&self, table: &Table, start: u64) -> Path {
+        self.root
+            .child(table.suffix)
+            .child(format!("range={}-{}", start, self.range_end(start)))
+            .child(PART_FILE)
+    }
+
+    /// Contents of a file, or `None` if it does not exist
+    async fn read_file(&self, path: &Path) -> Result<Option<Bytes>> {
+        match self.store.get(path).await {
+            Ok(file) => Ok(Some(file.bytes().await?)),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path)),
+        }
+    }
+
+    async fn read_rows(&self, table: &Table, start: u64) -> Result<Vec<Row>> {
+        let path = self.file_path(table, start);
+        match self.read_file(&path).await? {
+            Some(data) => decode(table, data).with_context(|| format!("Failed to decode {}", path)),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Load a written range back into blocks
+    async fn read_range(&self, start: u64) -> Result<BTreeMap<u64, IndexedBlock>> {
+        let mut blocks = BTreeMap::new();
+        for row in self.read_rows(&sql::BLOCKS, start).await? {
+            let block = sql::block_from_row(row)?;
+            blocks.insert(block.number, block);
+        }
+        for row in self.read_rows(&sql::TRANSACTIONS, start).await? {
+            let (block_number, transaction) = sql::transaction_from_row(row)?;
+            if let Some(block) = blocks.get_mut(&block_number) {
+                block.transactions.push(transaction);
+            }
+        }
+        if self.index_logs {
+            for row in self.read_rows(&sql::LOGS, start).await? {
+                let mut log = sql::log_from_row(row)?;
+                if let Some(block) = blocks.get_mut(&log.block_number) {
+                    log.block_hash = block.hash.clone();
+                    log.timestamp = block.timestamp;
+                    block.logs.push(log);
+                }
+            }
+        }
+        Ok(blocks)
+    }
+
+    async fn write_file(&self, table: &Table, start: u64, rows: Vec<Row>) -> Result<()> {
+        let path = self.file_path(table, start);
+        let data = encode(table, &rows).with_context(|| format!("Failed to encode {}", path))?;
+        self.store
+            .put(&path, PutPayload::from(data))
+            .await
+            .with_context(|| format!("Failed to write {}", path))?;
+        Ok(())
+    }
+
+    async fn write_range(&self, start: u64, blocks: &BTreeMap<u64, IndexedBlock>) -> Result<()> {
+        let transactions = blocks.values().flat_map(sql::transaction_rows).collect();
+        self.write_file(&sql::TRANSACTIONS, start, transactions)
+            .await?;
+        if self.index_logs {
+            let logs = blocks.values().flat_map(sql::log_rows).collect();
+            self.write_file(&sql::LOGS, start, logs).await?;
+        }
+        // Written last, the blocks file marks the range as finished
+        let rows = blocks.values().map(sql::block_row).collect();
+        self.write_file(&sql::BLOCKS, start, rows).await
+    }
+
+    async fn delete_range(&self, start: u64) -> Result<()> {
+        for table in [&sql::BLOCKS, &sql::TRANSACTIONS, &sql::LOGS] {
+            let path = self.file_path(table, start);
+            match self.store.delete(&path).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => {}
+                Err(e) => return Err(e).with_context(|| format!("Failed to delete {}", path)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the blocks of a range editable, loading them from its files if it was written
+    async fn pending_range<'a>(
+        &self,
+        state: &'a mut State,
+        start: u64,
+    ) -> Result<&'a mut BTreeMap<u64, IndexedBlock>> {
+        if !state.pending.contains_key(&start) {
+            let blocks = if state.written.contains(&start) {
+                self.read_range(start).await?
+            } else {
+                BTreeMap::new()
+            };
+            state.pending.insert(start, blocks);
+        }
+        Ok(state
+            .pending
+            .get_mut(&start)
+            .expect("range was just inserted"))
+    }
+
+    /// Write every buffered range that ends at or below the checkpoint
+    async fn flush(&self, state: &mut State, checkpoint: u64) -> Result<()> {
+        while let Some(entry) = state.pending.first_entry() {
+            let start = *entry.key();
+            if self.range_end(start) > checkpoint {
+                break;
+            }
+
+            self.write_range(start, entry.get()).await?;
+            let blocks = entry.remove().len();
+            state.written.insert(start);
+            log::info!(
+                "Wrote Parquet range {}-{} ({} blocks)",
+                start,
+                self.range_end(start),
+                blocks
+            );
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for ParquetSink {
+    fn name(&self) -> String {
+        "Parquet".to_string()
+    }
+
+    async fn create_indices(&self) -> Result<()> {
+        // Directories and files are created as ranges are written
+        Ok(())
+    }
+
+    async fn ping(&self) -> bool {
+        self.store
+            .list_with_delimiter(Some(&self.root))
+            .await
+            .is_ok()
+    }
+
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        let mut state = self.state.lock().await;
+        for block in blocks {
+            let range = self
+                .pending_range(&mut state, self.range_start(block.number))
+                .await?;
+            range.insert(block.number, block.clone());
+        }
+        Ok(())
+    }
+
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        let mut state = self.state.lock().await;
+        let mut deleted = 0;
+
+        let first_dropped = self.range_start(block_number) + self.range_size;
+        let dropped: Vec<u64> = state.written.range(first_dropped..).copied().collect();
+        for start in dropped {
+            let path = self.file_path(&sql::BLOCKS, start);
+            if let Some(data) = self.read_file(&path).await? {
+                deleted += decode_numbers(data)?.len() as u64;
+            }
+            self.delete_range(start).await?;
+            state.written.remove(&start);
+        }
+        for (_, blocks) in state.pending.split_off(&first_dropped) {
+            deleted += blocks.len() as u64;
+        }
+
+        let range = self
+            .pending_range(&mut state, self.range_start(block_number))
+            .await?;
+        deleted += range.split_off(&(block_number + 1)).len() as u64;
+
+        Ok(deleted)
+    }
+
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64> {
+        // Written files keep the finality their blocks had when the range was finished
+        let mut state = self.state.lock().await;
+        let mut updated = 0;
+        for blocks in state.pending.values_mut() {
+            for block in blocks.range_mut(..=block_number).map(|(_, b)| b) {
+                if block.finality < status {
+                    block.finality = status;
+                    updated += 1;
+                }
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn get_progress(&self) -> Result<SyncProgress> {
+        let path = self.root.child(PROGRESS_FILE);
+        match self.read_file(&path).await? {
+            Some(data) => {
+                serde_json::from_slice(&data).with_context(|| format!("Invalid {}", path))
+            }
+            None => Ok(SyncProgress::default()),
+        }
+    }
+
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
+        let mut state = self.state.lock().await;
+        self.flush(&mut state, progress.checkpoint()).await?;
+
+        // Blocks still buffered are lost on a restart, so the stored progress stops before them
+        let mut committed = progress.clone();
+        if let Some(&start) = state.pending.keys().next() {
+            committed.rewind(start.saturating_sub(1));
+        }
+
+        let path = self.root.child(PROGRESS_FILE);
+        self.store
+            .put(&path, PutPayload::from(serde_json::to_vec(&committed)?))
+            .await
+            .with_context(|| format!("Failed to write {}", path))?;
+        Ok(())
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        Ok(self
+            .get_blocks(&[block_number])
+            .await?
+            .remove(&block_number))
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        let mut ranges: BTreeMap<u64, Vec<u64>> = BTreeMap::new();
+        for &number in block_numbers {
+            ranges
+                .entry(self.range_start(number))
+                .or_default()
+                .push(number);
+        }
+
+        let state = self.state.lock().await;
+        let mut found = HashMap::with_capacity(block_numbers.len());
+        for (start, numbers) in ranges {
+            let loaded;
+            let blocks = match state.pending.get(&start) {
+                Some(blocks) => blocks,
+                None if state.written.contains(&start) => {
+                    loaded = self.read_range(start).await?;
+                    &loaded
+                }
+                None => continue,
+            };
+            for number in numbers {
+                if let Some(block) = blocks.get(&number) {
+                    found.insert(number, block.clone());
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        let state = self.state.lock().await;
+        let starts: BTreeSet<u64> = state
+            .written
+            .iter()
+            .chain(state.pending.keys())
+            .copied()
+            .filter(|&start| start <= to && self.range_end(start) >= from)
+            .collect();
+
+        let mut counts: HashMap<u64, u64> = HashMap::new();
+        for start in starts {
+            let numbers = match state.pending.get(&start) {
+                Some(blocks) => blocks.keys().copied().collect(),
+                None => match self.read_file(&self.file_path(&sql::BLOCKS, start)).await? {
+                    Some(data) => decode_numbers(data)?,
+                    None => Vec::new(),
+                },
+            };
+            for number in numbers.into_iter().filter(|n| (from..=to).contains(n)) {
+                *counts.entry(number - number % interval).or_default() += 1;
+            }
+        }
+        Ok(gaps::fill_buckets(from, to, interval, &counts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+
+    async fn open(dir: &std::path::Path) -> ParquetSink {
+        let config = Config::for_test(&[
+            ("SINKS", "parquet"),
+            ("PARQUET_PATH", dir.to_str().unwrap()),
+            ("PARQUET_RANGE_SIZE", "10"),
+        ]);
+        ParquetSink::open(&config).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_ranges_are_written_once_the_checkpoint_passes_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = open(dir.path()).await;
+
+        let blocks: Vec<IndexedBlock> = (0..25).map(test_block).collect();
+        sink.write_blocks_with_progress(&blocks, &SyncProgress::from_checkpoint(24))
+            .await
+            .unwrap();
+        assert!(dir.path().join("blocks/range=10-19/part.parquet").exists());
+        assert!(dir.path().join("logs/range=10-19/part.parquet").exists());
+        assert!(!dir.path().join("blocks/range=20-29/part.parquet").exists());
+
+        // Blocks 20-24 are only buffered, so a restart resumes before them
+        let sink = open(dir.path()).await;
+        assert_eq!(
+            sink.get_progress().await.unwrap(),
+            SyncProgress::from_checkpoint(19)
+        );
+        assert!(sink.get_block(22).await.unwrap().is_none());
+
+        let stored = sink.get_block(5).await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&stored).unwrap(),
+            serde_json::to_value(test_block(5)).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&stored.logs).unwrap(),
+            serde_json::to_value(&test_block(5).logs).unwrap()
+        );
+
+        let file =
+            std::fs::File::open(dir.path().join("transactions/range=0-9/part.parquet")).unwrap();
+        let schema = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .schema()
+            .clone();
+        assert_eq!(
+            schema.field_with_name("value").unwrap().data_type(),
+            &DataType::Decimal256(76, 0)
+        );
+        assert_eq!(
+            schema.field_with_name("nonce").unwrap().data_type(),
+            &DataType::Int64
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wei_amounts_above_u128() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = open(dir.path()).await;
+
+        let value = format!("{}000", u128::MAX);
+        let blocks: Vec<IndexedBlock> = (0..10)
+            .map(|n| {
+                let mut block = test_block(n);
+                block.transactions[0].value = value.clone();
+                block
+            })
+            .collect();
+        sink.write_blocks_with_progress(&blocks, &SyncProgress::from_checkpoint(9))
+            .await
+            .unwrap();
+        assert_eq!(
+            sink.get_progress().await.unwrap(),
+            SyncProgress::from_checkpoint(9)
+        );
+
+        let stored = sink.get_block(3).await.unwrap().unwrap();
+        assert_eq!(stored.transactions[0].value, value);
+    }
+
+    #[tokio::test]
+    async fn test_rollback_rewrites_the_affected_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let sink = open(dir.path()).await;
+        let blocks: Vec<IndexedBlock> = (0..30).map(test_block).collect();
+        sink.write_blocks_with_progress(&blocks, &SyncProgress::from_checkpoint(29))
+            .await
+            .unwrap();
+
+        assert_eq!(sink.delete_blocks_after(14).await.unwrap(), 15);
+        assert!(!dir.path().join("blocks/range=20-29/part.parquet").exists());
+        assert_eq!(
+            sink.block_count_histogram(0, 29, 10).await.unwrap(),
+            vec![(0, 10), (10, 5), (20, 0)]
+        );
+
+        let mut progress = SyncProgress::from_checkpoint(29);
+        progress.rewind(14);
+        sink.set_progress(&progress).await.unwrap();
+        assert_eq!(
+            sink.get_progress().await.unwrap(),
+            SyncProgress::from_checkpoint(9)
+        );
+
+        sink.write_blocks_with_progress(
+            &(15..20).map(test_block).collect::<Vec<_>>(),
+            &SyncProgress::from_checkpoint(19),
+        )
+        .await
+        .unwrap();
+        let sink = open(dir.path()).await;
+        let stored = sink.get_blocks(&[9, 10, 19, 20]).await.unwrap();
+        let mut numbers: Vec<u64> = stored.into_keys().collect();
+        numbers.sort();
+        assert_eq!(numbers, vec![9, 10, 19]);
+    }
+}
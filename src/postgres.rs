@@ -0,0 +1,572 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::SinkExt;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::sync::{Mutex, MutexGuard};
+use tokio_postgres::types::ToSql;
+use tokio_postgres::{Client, NoTls, Transaction};
+
+use crate::config::Config;
+use crate::gaps;
+use crate::models::{FinalityStatus, IndexedBlock};
+use crate::progress::SyncProgress;
+use crate::sink::Sink;
+use crate::sql::{self, ident, ColumnType, Row, Table};
+
+/// Batches of at least this many blocks are loaded with COPY, smaller ones with upserts
+const COPY_MIN_BLOCKS: usize = 16;
+
+/// A connection busy for longer than this is reported as unreachable
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Writes blocks, transactions and logs into normalized PostgreSQL tables.
+///
+/// The sync progress is kept in the same database. Live sync commits each block together with
+/// the checkpoint covering it. Backfill commits the checkpoint in its own transaction after the
+/// batch is stored, so a crash in between only means the batch is loaded again.
+pub struct PostgresSink {
+    url: String,
+    client: Mutex<Client>,
+    blocks_table: String,
+    transactions_table: String,
+    logs_table: String,
+    progress_table: String,
+    index_logs: bool,
+}
+
+fn sql_type(kind: ColumnType) -> &'static str {
+    match kind {
+        ColumnType::Integer => "BIGINT",
+        ColumnType::Numeric => "NUMERIC",
+        ColumnType::Text => "TEXT",
+        ColumnType::Boolean => "BOOLEAN",
+        ColumnType::Json => "JSONB",
+    }
+}
+
+/// INSERT of one row, every column passed as text, replacing the row with the same key
+fn upsert_sql(table: &Table, name: &str) -> String {
+    let columns = table.column_names();
+    let values: Vec<String> = table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("${}::text::{}", i + 1, sql_type(column.kind)))
+        .collect();
+    let updates: Vec<String> = columns
+        .iter()
+        .filter(|column| !table.key.contains(column))
+        .map(|column| format!("{} = EXCLUDED.{}", column, column))
+        .collect();
+
+    format!(
+        "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) DO UPDATE SET {}",
+        ident(name),
+        columns.join(", "),
+        values.join(", "),
+        table.key.join(", "),
+        updates.join(", ")
+    )
+}
+
+/// Columns of `table` read back as text, in table order
+fn select_columns(table: &Table) -> String {
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .map(|column| format!("{}::text", column.name))
+        .collect();
+    columns.join(", ")
+}
+
+/// Encode rows as COPY CSV, where an unquoted empty field is NULL
+fn csv(rows: &[Row]) -> String {
+    let mut out = String::new();
+    for row in rows {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            if let Some(value) = value {
+                out.push('"');
+                out.push_str(&value.replace('"', "\"\""));
+                out.push('"');
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn text_row(row: &tokio_postgres::Row) -> Row {
+    (0..row.len()).map(|i| row.get(i)).collect()
+}
+
+async fn connect(url: &str) -> Result<Client> {
+    let (client, connection) = tokio_postgres::connect(url, NoTls)
+        .await
+        .context("Failed to connect to PostgreSQL")?;
+    tokio::spawn(async move {
+        if let Err(e) = connection.await {
+            log::warn!("PostgreSQL connection closed: {}", e);
+        }
+    });
+    Ok(client)
+}
+
+async fn copy_rows(tx: &Transaction<'_>, table: &Table, name: &str, rows: &[Row]) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let statement = format!(
+        "COPY {} ({}) FROM STDIN (FORMAT csv)",
+        ident(name),
+        table.column_names().join(", ")
+    );
+    let sink = tx.copy_in::<_, Bytes>(statement.as_str()).await?;
+    futures::pin_mut!(sink);
+    sink.send(Bytes::from(csv(rows))).await?;
+    sink.finish().await?;
+    Ok(())
+}
+
+async fn upsert_rows(tx: &Transaction<'_>, table: &Table, name: &str, rows: &[Row]) -> Result<()> {
+    if rows.is_empty() {
+        return Ok(());
+    }
+
+    let statement = tx.prepare(&upsert_sql(table, name)).await?;
+    for row in rows {
+        let params: Vec<&(dyn ToSql + Sync)> =
+            row.iter().map(|v| v as &(dyn ToSql + Sync)).collect();
+        tx.execute(&statement, &params).await?;
+    }
+    Ok(())
+}
+
+impl PostgresSink {
+    pub async fn connect(config: &Config) -> Result<Self> {
+        let url = config
+            .postgres_url
+            .clone()
+            .context("POSTGRES_URL is required by the postgres sink")?;
+        let client = connect(&url).await?;
+
+        Ok(PostgresSink {
+            url,
+            client: Mutex::new(client),
+            blocks_table: config.table_name(sql::BLOCKS.suffix),
+            transactions_table: config.table_name(sql::TRANSACTIONS.suffix),
+            logs_table: config.table_name(sql::LOGS.suffix),
+            progress_table: config.table_name("progress"),
+            index_logs: config.index_receipts && config.index_logs,
+        })
+    }
+
+    /// The connection, re-established if it was closed
+    async fn client(&self) -> Result<MutexGuard<'_, Client>> {
+        let mut client = self.client.lock().await;
+        if client.is_closed() {
+            log::warn!("Reconnecting to PostgreSQL");
+            *client = connect(&self.url).await?;
+        }
+        Ok(client)
+    }
+
+    /// Write blocks and, when given, the progress in a single transaction
+    async fn write(&self, blocks: &[IndexedBlock], progress: Option<&SyncProgress>) -> Result<()> {
+        let block_rows: Vec<Row> = blocks.iter().map(sql::block_row).collect();
+        let transaction_rows: Vec<Row> = blocks.iter().flat_map(sql::transaction_rows).collect();
+        let log_rows: Vec<Row> = if self.index_logs {
+            blocks.iter().flat_map(sql::log_rows).collect()
+        } else {
+            Vec::new()
+        };
+        let numbers: Vec<i64> = blocks.iter().map(|b| b.number as i64).collect();
+
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+
+        if blocks.len() >= COPY_MIN_BLOCKS {
+            // Backfill: drop stored copies, which takes their transactions and logs along,
+            // then bulk load
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE number = ANY($1)",
+                    ident(&self.blocks_table)
+                ),
+                &[&numbers],
+            )
+            .await?;
+            copy_rows(&tx, &sql::BLOCKS, &self.blocks_table, &block_rows).await?;
+            copy_rows(
+                &tx,
+                &sql::TRANSACTIONS,
+                &self.transactions_table,
+                &transaction_rows,
+            )
+            .await?;
+            copy_rows(&tx, &sql::LOGS, &self.logs_table, &log_rows).await?;
+        } else if !blocks.is_empty() {
+            // Live sync: upsert the blocks and replace their transactions and logs
+            upsert_rows(&tx, &sql::BLOCKS, &self.blocks_table, &block_rows).await?;
+            let mut children = vec![&self.transactions_table];
+            if self.index_logs {
+                children.push(&self.logs_table);
+            }
+            for table in children {
+                tx.execute(
+                    &format!("DELETE FROM {} WHERE block_number = ANY($1)", ident(table)),
+                    &[&numbers],
+                )
+                .await?;
+            }
+            upsert_rows(
+                &tx,
+                &sql::TRANSACTIONS,
+                &self.transactions_table,
+                &transaction_rows,
+            )
+            .await?;
+            upsert_rows(&tx, &sql::LOGS, &self.logs_table, &log_rows).await?;
+        }
+
+        if let Some(progress) = progress {
+            self.save_progress(&tx, progress).await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn save_progress(&self, tx: &Transaction<'_>, progress: &SyncProgress) -> Result<()> {
+        let failed: Vec<i64> = progress.failed_blocks.iter().map(|&b| b as i64).collect();
+        tx.execute(
+            &format!(
+                "INSERT INTO {} (id, scanned_through, checkpoint, failed_blocks, updated_at) \
+                 VALUES (1, $1, $2, $3, now()) \
+                 ON CONFLICT (id) DO UPDATE SET scanned_through = EXCLUDED.scanned_through, \
+                 checkpoint = EXCLUDED.checkpoint, failed_blocks = EXCLUDED.failed_blocks, \
+                 updated_at = EXCLUDED.updated_at",
+                ident(&self.progress_table)
+            ),
+            &[
+                &(progress.scanned_through as i64),
+                &(progress.checkpoint() as i64),
+                &failed,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for PostgresSink {
+    fn name(&self) -> String {
+        "PostgreSQL".to_string()
+    }
+
+    async fn create_indices(&self) -> Result<()> {
+        let mut tables = vec![
+            (&sql::BLOCKS, &self.blocks_table),
+            (&sql::TRANSACTIONS, &self.transactions_table),
+        ];
+        if self.index_logs {
+            tables.push((&sql::LOGS, &self.logs_table));
+        }
+        let mut statements: Vec<String> = tables
+            .into_iter()
+            .map(|(table, name)| sql::create_table_sql(table, name, &self.blocks_table, sql_type))
+            .collect();
+        statements.push(format!(
+            "CREATE TABLE IF NOT EXISTS {} (\
+             id INTEGER PRIMARY KEY CHECK (id = 1), \
+             scanned_through BIGINT NOT NULL, \
+             checkpoint BIGINT NOT NULL, \
+             failed_blocks BIGINT[] NOT NULL, \
+             updated_at TIMESTAMPTZ NOT NULL)",
+            ident(&self.progress_table)
+        ));
+
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        tx.batch_execute(&statements.join(";\n")).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> bool {
+        let ping = async {
+            let client = self.client.lock().await;
+            !client.is_closed() && client.simple_query("SELECT 1").await.is_ok()
+        };
+        tokio::time::timeout(PING_TIMEOUT, ping)
+            .await
+            .unwrap_or(false)
+    }
+
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        self.write(blocks, None).await
+    }
+
+    async fn write_blocks_with_progress(
+        &self,
+        blocks: &[IndexedBlock],
+        progress: &SyncProgress,
+    ) -> Result<()> {
+        self.write(blocks, Some(progress)).await
+    }
+
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        let client = self.client().await?;
+        let deleted = client
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE number > $1",
+                    ident(&self.blocks_table)
+                ),
+                &[&(block_number as i64)],
+            )
+            .await?;
+        log::debug!(
+            "Deleted {} block(s) above {} from PostgreSQL",
+            deleted,
+            block_number
+        );
+        Ok(deleted)
+    }
+
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64> {
+        let settled: &[&str] = match status {
+            FinalityStatus::Unfinalized => return Ok(0),
+            FinalityStatus::Safe => &["safe", "finalized"],
+            FinalityStatus::Finalized => &["finalized"],
+        };
+
+        let client = self.client().await?;
+        let updated = client
+            .execute(
+                &format!(
+                    "UPDATE {} SET finality = $1 WHERE number <= $2 AND finality <> ALL($3)",
+                    ident(&self.blocks_table)
+                ),
+                &[&status.as_str(), &(block_number as i64), &settled],
+            )
+            .await?;
+        Ok(updated)
+    }
+
+    async fn get_progress(&self) -> Result<SyncProgress> {
+        let client = self.client().await?;
+        let row = client
+            .query_opt(
+                &format!(
+                    "SELECT scanned_through, failed_blocks FROM {} WHERE id = 1",
+                    ident(&self.progress_table)
+                ),
+                &[],
+            )
+            .await?;
+
+        Ok(match row {
+            Some(row) => SyncProgress {
+                scanned_through: row.get::<_, i64>(0) as u64,
+                failed_blocks: row
+                    .get::<_, Vec<i64>>(1)
+                    .into_iter()
+                    .map(|b| b as u64)
+                    .collect(),
+            },
+            None => SyncProgress::default(),
+        })
+    }
+
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
+        let mut client = self.client().await?;
+        let tx = client.transaction().await?;
+        self.save_progress(&tx, progress).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        Ok(self
+            .get_blocks(&[block_number])
+            .await?
+            .remove(&block_number))
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        let numbers: Vec<i64> = block_numbers.iter().map(|&n| n as i64).collect();
+        let client = self.client().await?;
+
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT {} FROM {} WHERE number = ANY($1)",
+                    select_columns(&sql::BLOCKS),
+                    ident(&self.blocks_table)
+                ),
+                &[&numbers],
+            )
+            .await?;
+        let mut blocks = HashMap::with_capacity(rows.len());
+        for row in &rows {
+            let block = sql::block_from_row(text_row(row))?;
+            blocks.insert(block.number, block);
+        }
+
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT {} FROM {} WHERE block_number = ANY($1) \
+                     ORDER BY block_number, transaction_index",
+                    select_columns(&sql::TRANSACTIONS),
+                    ident(&self.transactions_table)
+                ),
+                &[&numbers],
+            )
+            .await?;
+        for row in &rows {
+            let (block_number, transaction) = sql::transaction_from_row(text_row(row))?;
+            if let Some(block) = blocks.get_mut(&block_number) {
+                block.transactions.push(transaction);
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        let client = self.client().await?;
+        let rows = client
+            .query(
+                &format!(
+                    "SELECT number / $3 * $3, count(*) FROM {} \
+                     WHERE number BETWEEN $1 AND $2 GROUP BY 1",
+                    ident(&self.blocks_table)
+                ),
+                &[&(from as i64), &(to as i64), &(interval as i64)],
+            )
+            .await?;
+
+        let counts: HashMap<u64, u64> = rows
+            .iter()
+            .map(|row| (row.get::<_, i64>(0) as u64, row.get::<_, i64>(1) as u64))
+            .collect();
+        Ok(gaps::fill_buckets(from, to, interval, &counts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+
+    #[test]
+    fn test_csv_distinguishes_null_from_empty() {
+        let rows = vec![
+            vec![Some("1".to_string()), None, Some(String::new())],
+            vec![Some("say \"hi\", twice".to_string()), None, None],
+        ];
+        assert_eq!(csv(&rows), "\"1\",,\"\"\n\"say \"\"hi\"\", twice\",,\n");
+    }
+
+    #[test]
+    fn test_upsert_sql() {
+        let sql = upsert_sql(&sql::LOGS, "chain_logs");
+        assert!(sql.starts_with("INSERT INTO \"chain_logs\" (block_number, log_index,"));
+        assert!(sql.contains("$1::text::BIGINT"));
+        assert!(sql.contains("ON CONFLICT (block_number, log_index) DO UPDATE SET"));
+        assert!(!sql.contains("log_index = EXCLUDED.log_index"));
+        assert!(sql.ends_with("removed = EXCLUDED.removed"));
+    }
+
+    /// Runs against the database in `TEST_POSTGRES_URL`, and is skipped when it is unset
+    #[tokio::test]
+    async fn test_round_trip() {
+        let Ok(url) = std::env::var("TEST_POSTGRES_URL") else {
+            return;
+        };
+        let prefix = format!("test{}", std::process::id());
+        let config = Config::for_test(&[
+            ("SINKS", "postgres"),
+            ("POSTGRES_URL", url.as_str()),
+            ("INDEX_PREFIX", prefix.as_str()),
+        ]);
+        let sink = PostgresSink::connect(&config).await.unwrap();
+        sink.create_indices().await.unwrap();
+
+        // COPY for the backfill, then upserts over part of it
+        let blocks: Vec<IndexedBlock> = (0..40).map(test_block).collect();
+        sink.write_blocks(&blocks).await.unwrap();
+        let mut progress = SyncProgress::default();
+        progress.record(40, &[]);
+        let mut block = test_block(40);
+        block.hash = "0xreplaced".to_string();
+        sink.write_blocks_with_progress(&[block.clone()], &progress)
+            .await
+            .unwrap();
+        block.hash = format!("0x{:064x}", 40);
+        sink.write_blocks(&[block]).await.unwrap();
+
+        assert_eq!(sink.get_progress().await.unwrap(), progress);
+        let stored = sink.get_block(40).await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&stored).unwrap(),
+            serde_json::to_value(test_block(40)).unwrap()
+        );
+
+        assert_eq!(
+            sink.update_finality(10, FinalityStatus::Finalized)
+                .await
+                .unwrap(),
+            11
+        );
+        assert_eq!(
+            sink.update_finality(20, FinalityStatus::Safe)
+                .await
+                .unwrap(),
+            10
+        );
+        assert_eq!(sink.delete_blocks_after(29).await.unwrap(), 11);
+        assert_eq!(
+            sink.block_count_histogram(0, 49, 25).await.unwrap(),
+            vec![(0, 25), (25, 5)]
+        );
+
+        let client = sink.client().await.unwrap();
+        let orphans = client
+            .query_one(
+                &format!(
+                    "SELECT count(*) FROM {} WHERE block_number > 29",
+                    ident(&sink.transactions_table)
+                ),
+                &[],
+            )
+            .await
+            .unwrap();
+        assert_eq!(orphans.get::<_, i64>(0), 0);
+
+        for table in [
+            &sink.logs_table,
+            &sink.transactions_table,
+            &sink.blocks_table,
+            &sink.progress_table,
+        ] {
+            client
+                .batch_execute(&format!("DROP TABLE {}", ident(table)))
+                .await
+                .unwrap();
+        }
+    }
+}
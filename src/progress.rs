@@ -0,0 +1,142 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Indexing progress persisted alongside the checkpoint.
+///
+/// `scanned_through` is the highest block that has been processed and `failed_blocks` are the
+/// blocks at or below it that could not be indexed. The checkpoint is derived from both, so it
+/// never moves past a failed block until that block is re-indexed or explicitly skipped.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncProgress {
+    pub scanned_through: u64,
+    #[serde(default)]
+    pub failed_blocks: BTreeSet<u64>,
+}
+
+impl SyncProgress {
+    /// Progress of an index written before failures were tracked
+    pub fn from_checkpoint(checkpoint: u64) -> Self {
+        SyncProgress {
+            scanned_through: checkpoint,
+            failed_blocks: BTreeSet::new(),
+        }
+    }
+
+    /// Highest block such that every block up to it is indexed
+    pub fn checkpoint(&self) -> u64 {
+        match self.failed_blocks.first() {
+            Some(&lowest) => lowest.saturating_sub(1),
+            None => self.scanned_through,
+        }
+    }
+
+    /// Record that every block up to `to` was processed, except for `failed`
+    pub fn record(&mut self, to: u64, failed: &[u64]) {
+        self.scanned_through = self.scanned_through.max(to);
+        self.failed_blocks.extend(failed.iter().copied());
+    }
+
+    /// Mark a previously failed block as indexed
+    pub fn resolve(&mut self, block_number: u64) -> bool {
+        self.failed_blocks.remove(&block_number)
+    }
+
+    /// Drop blocks from the retry list on operator request, returning the ones that were pending
+    pub fn skip(&mut self, block_numbers: &[u64]) -> Vec<u64> {
+        block_numbers
+            .iter()
+            .copied()
+            .filter(|block_number| self.failed_blocks.remove(block_number))
+            .collect()
+    }
+
+    /// Set the scanned range to end at `block_number` on operator request, forgetting failures
+    /// above it. Unlike `rewind` this can also move progress forward.
+    pub fn reset(&mut self, block_number: u64) {
+        self.scanned_through = block_number;
+        self.failed_blocks.retain(|&b| b <= block_number);
+    }
+
+    /// Forget everything above `block_number`, e.g. after a reorg
+    pub fn rewind(&mut self, block_number: u64) {
+        self.scanned_through = self.scanned_through.min(block_number);
+        self.failed_blocks.retain(|&b| b <= block_number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkpoint_without_failures() {
+        let mut progress = SyncProgress::from_checkpoint(100);
+        progress.record(200, &[]);
+
+        assert_eq!(progress.checkpoint(), 200);
+    }
+
+    #[test]
+    fn test_checkpoint_holds_at_lowest_failure() {
+        let mut progress = SyncProgress::from_checkpoint(100);
+        progress.record(200, &[150, 120]);
+        progress.record(300, &[250]);
+
+        assert_eq!(progress.scanned_through, 300);
+        assert_eq!(progress.checkpoint(), 119);
+
+        assert!(progress.resolve(120));
+        assert_eq!(progress.checkpoint(), 149);
+
+        assert!(!progress.resolve(120));
+        progress.resolve(150);
+        progress.resolve(250);
+        assert_eq!(progress.checkpoint(), 300);
+    }
+
+    #[test]
+    fn test_skip_only_reports_pending_blocks() {
+        let mut progress = SyncProgress::from_checkpoint(0);
+        progress.record(10, &[3, 7]);
+
+        assert_eq!(progress.skip(&[7, 9]), vec![7]);
+        assert_eq!(progress.checkpoint(), 2);
+    }
+
+    #[test]
+    fn test_rewind_drops_progress_above_ancestor() {
+        let mut progress = SyncProgress::from_checkpoint(0);
+        progress.record(100, &[40, 90]);
+        progress.rewind(80);
+
+        assert_eq!(progress.scanned_through, 80);
+        assert_eq!(progress.failed_blocks.len(), 1);
+        assert_eq!(progress.checkpoint(), 39);
+    }
+
+    #[test]
+    fn test_reset_moves_both_ways() {
+        let mut progress = SyncProgress::from_checkpoint(0);
+        progress.record(100, &[40, 90]);
+
+        progress.reset(500);
+        assert_eq!(progress.scanned_through, 500);
+        assert_eq!(progress.checkpoint(), 39);
+
+        progress.reset(50);
+        assert_eq!(progress.scanned_through, 50);
+        assert_eq!(progress.failed_blocks.len(), 1);
+    }
+
+    #[test]
+    fn test_progress_serialization() {
+        let mut progress = SyncProgress::from_checkpoint(5);
+        progress.record(10, &[8, 6]);
+
+        let json = serde_json::to_string(&progress).unwrap();
+        assert_eq!(json, r#"{"scanned_through":10,"failed_blocks":[6,8]}"#);
+
+        let deserialized: SyncProgress = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, progress);
+    }
+}
@@ -0,0 +1,448 @@
+use anyhow::{Context, Result};
+use ethers::middleware::Middleware;
+use ethers::providers::Provider;
+use log::{debug, info, warn};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+use crate::config::Config;
+use crate::error::IndexerError;
+use crate::metrics::METRICS;
+use crate::throttle::AdaptiveConcurrency;
+use crate::transport::ThrottledHttp;
+
+/// Weight of the newest sample in the latency and error rate moving averages
+const EWMA_ALPHA: f64 = 0.2;
+
+/// Latency assumed for an endpoint before its first successful call
+const INITIAL_LATENCY_MS: f64 = 100.0;
+
+/// How often the heads of all endpoints are compared
+const HEAD_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Delay before the first retry of a transient RPC error; doubled on every further attempt
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// Upper bound of the delay between retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// One RPC node of the pool
+pub struct RpcEndpoint {
+    pub url: String,
+    pub provider: Provider<ThrottledHttp>,
+    /// Cleared once the node rejects `eth_getBlockReceipts`
+    pub block_receipts_supported: AtomicBool,
+}
+
+/// Rolling health statistics of an RPC endpoint
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointHealth {
+    /// Moving average of call latency in milliseconds
+    pub latency_ms: f64,
+    /// Moving average of the share of failed calls, between 0 and 1
+    pub error_rate: f64,
+    /// Last head block reported by the endpoint
+    pub head: Option<u64>,
+    /// Set while the endpoint's head lags too far behind the others
+    pub ejected: bool,
+}
+
+impl Default for EndpointHealth {
+    fn default() -> Self {
+        EndpointHealth {
+            latency_ms: INITIAL_LATENCY_MS,
+            error_rate: 0.0,
+            head: None,
+            ejected: false,
+        }
+    }
+}
+
+impl EndpointHealth {
+    pub fn record_success(&mut self, latency: Duration) {
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        self.latency_ms += EWMA_ALPHA * (latency_ms - self.latency_ms);
+        self.error_rate -= EWMA_ALPHA * self.error_rate;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.error_rate += EWMA_ALPHA * (1.0 - self.error_rate);
+    }
+
+    /// Relative share of calls routed to the endpoint; faster and more reliable nodes get more
+    pub fn weight(&self) -> f64 {
+        (1.0 - self.error_rate).max(0.01) / self.latency_ms.max(1.0)
+    }
+}
+
+/// Eject endpoints whose head is more than `max_lag` blocks behind the highest known head.
+/// Endpoints that did not report a head are ejected as long as another one did.
+pub fn eject_lagging(healths: &mut [EndpointHealth], max_lag: u64) {
+    let best = healths.iter().filter_map(|h| h.head).max();
+
+    for health in healths {
+        health.ejected = match (health.head, best) {
+            (Some(head), Some(best)) => best - head > max_lag,
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+    }
+}
+
+/// Order in which endpoints are tried for one call: healthy endpoints drawn at random in
+/// proportion to their weight, then ejected endpoints as a last resort. `roll` returns
+/// uniform values in `0.0..1.0`.
+pub fn call_order(healths: &[EndpointHealth], mut roll: impl FnMut() -> f64) -> Vec<usize> {
+    let mut candidates: Vec<(usize, f64)> = healths
+        .iter()
+        .enumerate()
+        .filter(|(_, h)| !h.ejected)
+        .map(|(index, h)| (index, h.weight()))
+        .collect();
+
+    let mut order = Vec::with_capacity(healths.len());
+    while !candidates.is_empty() {
+        let total: f64 = candidates.iter().map(|(_, weight)| weight).sum();
+        let mut target = roll() * total;
+        let picked = candidates
+            .iter()
+            .position(|(_, weight)| {
+                if target < *weight {
+                    return true;
+                }
+                target -= weight;
+                false
+            })
+            .unwrap_or(candidates.len() - 1);
+        order.push(candidates.remove(picked).0);
+    }
+
+    order.extend(
+        healths
+            .iter()
+            .enumerate()
+            .filter(|(_, h)| h.ejected)
+            .map(|(index, _)| index),
+    );
+    order
+}
+
+/// Delay before retry number `attempt` (starting at 0): exponential backoff with jitter,
+/// where `roll` in `0.0..1.0` picks a point in the upper half of the window
+pub fn retry_delay(attempt: u32, roll: f64) -> Duration {
+    let delay = RETRY_BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(RETRY_MAX_DELAY);
+    delay.mul_f64(0.5 + roll / 2.0)
+}
+
+/// Whether an error returned from an RPC call is worth retrying
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<IndexerError>()
+        .is_some_and(IndexerError::is_transient)
+}
+
+/// `IndexerError::kind` of an error returned from an RPC call, or "other" if unclassified
+pub fn error_kind(error: &anyhow::Error) -> &'static str {
+    error
+        .downcast_ref::<IndexerError>()
+        .map_or("other", IndexerError::kind)
+}
+
+/// Pool of RPC endpoints with health tracking, weighted load-balancing and failover
+pub struct RpcPool {
+    endpoints: Vec<Arc<RpcEndpoint>>,
+    health: Mutex<Vec<EndpointHealth>>,
+    max_head_lag: u64,
+    max_retries: u32,
+    /// xorshift state for the weighted draw
+    rng: AtomicU64,
+}
+
+impl RpcPool {
+    /// Pool over every configured HTTP endpoint. Each endpoint gets its own rate limit, and all
+    /// of them report to the shared `concurrency` controller.
+    pub fn new(config: &Config, concurrency: &Arc<AdaptiveConcurrency>) -> Result<Self> {
+        let timeout = Duration::from_secs(config.rpc_timeout_secs);
+
+        let endpoints = config
+            .rpc_endpoints()
+            .into_iter()
+            .map(|url| {
+                let parsed = url
+                    .parse()
+                    .with_context(|| format!("Failed to create RPC provider for {}", url))?;
+                let provider = Provider::new(ThrottledHttp::new(
+                    parsed,
+                    config.rpc_rate_limit,
+                    timeout,
+                    config.rpc_batch_size,
+                    Arc::clone(concurrency),
+                ));
+                Ok(Arc::new(RpcEndpoint {
+                    url: url.to_string(),
+                    provider,
+                    block_receipts_supported: AtomicBool::new(true),
+                }))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
+        Ok(RpcPool {
+            health: Mutex::new(vec![EndpointHealth::default(); endpoints.len()]),
+            endpoints,
+            max_head_lag: config.rpc_max_head_lag,
+            max_retries: config.rpc_max_retries,
+            rng: AtomicU64::new(seed | 1),
+        })
+    }
+
+    /// Run `call` against the endpoints in weighted random order until one succeeds. When
+    /// every endpoint fails with a transient `IndexerError`, the round is retried with
+    /// jittered exponential backoff up to `RPC_MAX_RETRIES` times.
+    pub async fn call<T, F, Fut>(&self, call: F) -> Result<T>
+    where
+        F: Fn(Arc<RpcEndpoint>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 0;
+        loop {
+            match self.call_once(&call).await {
+                Err(e) if attempt < self.max_retries && is_transient(&e) => {
+                    let delay = retry_delay(attempt, self.next_roll());
+                    attempt += 1;
+                    METRICS.rpc_retries.inc();
+                    debug!(
+                        "Transient RPC error, retrying in {:?} (attempt {}/{}): {:#}",
+                        delay, attempt, self.max_retries, e
+                    );
+                    sleep(delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// One failover round over the endpoints
+    async fn call_once<T, F, Fut>(&self, call: &F) -> Result<T>
+    where
+        F: Fn(Arc<RpcEndpoint>) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let order = {
+            let health = self.health.lock().unwrap();
+            call_order(&health, || self.next_roll())
+        };
+
+        let mut last_error = None;
+        for (attempt, &index) in order.iter().enumerate() {
+            let endpoint = Arc::clone(&self.endpoints[index]);
+            let started = Instant::now();
+
+            match call(endpoint).await {
+                Ok(value) => {
+                    self.health.lock().unwrap()[index].record_success(started.elapsed());
+                    return Ok(value);
+                }
+                Err(e) => {
+                    self.health.lock().unwrap()[index].record_failure();
+                    if attempt + 1 < order.len() {
+                        warn!(
+                            "RPC call to {} failed, failing over: {:#}",
+                            self.endpoints[index].url, e
+                        );
+                    }
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        Err(last_error.expect("RPC pool has at least one endpoint"))
+    }
+
+    /// Query the head of every endpoint and eject the ones lagging more than `max_head_lag`
+    /// blocks behind the highest head
+    pub async fn refresh_heads(&self) {
+        let heads = futures::future::join_all(self.endpoints.iter().map(|endpoint| async move {
+            let started = Instant::now();
+            let head = endpoint.provider.get_block_number().await;
+            (head, started.elapsed())
+        }))
+        .await;
+
+        let mut healths = self.health.lock().unwrap();
+        let was_ejected: Vec<bool> = healths.iter().map(|h| h.ejected).collect();
+
+        for ((endpoint, health), (head, latency)) in
+            self.endpoints.iter().zip(healths.iter_mut()).zip(heads)
+        {
+            match head {
+                Ok(head) => {
+                    health.head = Some(head.as_u64());
+                    health.record_success(latency);
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to fetch head from RPC endpoint {}: {}",
+                        endpoint.url, e
+                    );
+                    health.head = None;
+                    health.record_failure();
+                }
+            }
+        }
+
+        eject_lagging(&mut healths, self.max_head_lag);
+        if let Some(best) = healths.iter().filter_map(|h| h.head).max() {
+            METRICS.set_head_block(best);
+        }
+
+        for ((endpoint, health), was_ejected) in
+            self.endpoints.iter().zip(healths.iter()).zip(was_ejected)
+        {
+            debug!(
+                "RPC endpoint {}: head {:?}, latency {:.0}ms, error rate {:.2}",
+                endpoint.url, health.head, health.latency_ms, health.error_rate
+            );
+            match (was_ejected, health.ejected) {
+                (false, true) => warn!(
+                    "Ejecting RPC endpoint {} (head {:?} is more than {} blocks behind)",
+                    endpoint.url, health.head, self.max_head_lag
+                ),
+                (true, false) => info!("RPC endpoint {} is back in rotation", endpoint.url),
+                _ => {}
+            }
+        }
+    }
+
+    /// Highest head reported by any endpoint at the last refresh
+    pub fn best_head(&self) -> Option<u64> {
+        self.health
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|h| h.head)
+            .max()
+    }
+
+    /// Whether an endpoint in rotation answered the last head refresh
+    pub fn is_available(&self) -> bool {
+        self.health
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|h| h.head.is_some() && !h.ejected)
+    }
+
+    /// Periodically refresh endpoint heads in the background, keeping the head block metric
+    /// current even while a long backfill runs
+    pub fn spawn_head_monitor(self: &Arc<Self>) {
+        let pool = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                sleep(HEAD_CHECK_INTERVAL).await;
+                pool.refresh_heads().await;
+            }
+        });
+    }
+
+    fn next_roll(&self) -> f64 {
+        let mut state = self.rng.load(Ordering::Relaxed);
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        self.rng.store(state, Ordering::Relaxed);
+
+        (state >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(latency_ms: f64, head: Option<u64>) -> EndpointHealth {
+        EndpointHealth {
+            latency_ms,
+            head,
+            ..EndpointHealth::default()
+        }
+    }
+
+    #[test]
+    fn test_health_scoring() {
+        let mut health = EndpointHealth::default();
+        let initial = health.weight();
+
+        health.record_failure();
+        assert!((health.error_rate - 0.2).abs() < 1e-9);
+        assert!(health.weight() < initial);
+
+        health.record_success(Duration::from_millis(100));
+        assert!((health.error_rate - 0.16).abs() < 1e-9);
+
+        let mut fast = EndpointHealth::default();
+        fast.record_success(Duration::from_millis(10));
+        assert!((fast.latency_ms - 82.0).abs() < 1e-9);
+        assert!(fast.weight() > initial);
+    }
+
+    #[test]
+    fn test_eject_lagging() {
+        let mut healths = vec![
+            health(100.0, Some(1000)),
+            health(100.0, Some(995)),
+            health(100.0, Some(990)),
+            health(100.0, None),
+        ];
+        eject_lagging(&mut healths, 5);
+
+        let ejected: Vec<bool> = healths.iter().map(|h| h.ejected).collect();
+        assert_eq!(ejected, vec![false, false, true, true]);
+
+        // Without any known head nothing is ejected
+        let mut unknown = vec![health(100.0, None), health(100.0, None)];
+        eject_lagging(&mut unknown, 5);
+        assert!(unknown.iter().all(|h| !h.ejected));
+    }
+
+    #[test]
+    fn test_call_order_prefers_heavier_endpoints() {
+        let mut healths = vec![
+            health(100.0, None),
+            health(300.0, None),
+            health(100.0, None),
+        ];
+        healths[2].ejected = true;
+
+        // Weights are 0.01 and 0.0033: a roll below 0.75 picks the faster endpoint first
+        assert_eq!(call_order(&healths, || 0.5), vec![0, 1, 2]);
+        assert_eq!(call_order(&healths, || 0.9), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(0, 0.0), Duration::from_millis(125));
+        assert_eq!(retry_delay(0, 1.0), Duration::from_millis(250));
+        assert_eq!(retry_delay(2, 1.0), Duration::from_secs(1));
+        assert_eq!(retry_delay(10, 1.0), RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX, 0.0), RETRY_MAX_DELAY / 2);
+    }
+
+    #[test]
+    fn test_call_order_uses_ejected_endpoints_last() {
+        let mut healths = vec![health(100.0, None), health(100.0, None)];
+        healths[0].ejected = true;
+        healths[1].ejected = true;
+
+        assert_eq!(call_order(&healths, || 0.0), vec![0, 1]);
+    }
+}
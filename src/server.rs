@@ -0,0 +1,58 @@
+use anyhow::{Context, Result};
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use log::info;
+use serde::Serialize;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use crate::indexer::BlockIndexer;
+use crate::metrics::METRICS;
+
+/// Serve `/metrics`, `/healthz` and `/readyz` until the process exits
+pub async fn serve(addr: SocketAddr, indexer: Arc<BlockIndexer>) -> Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(indexer);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind HTTP server to {}", addr))?;
+    info!("Serving metrics and health checks on http://{}", addr);
+
+    axum::serve(listener, app)
+        .await
+        .context("HTTP server failed")
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        METRICS.render(),
+    )
+}
+
+/// The process is up; reports the sync state without probing the backends
+async fn healthz(State(indexer): State<Arc<BlockIndexer>>) -> Response {
+    json_response(StatusCode::OK, &indexer.sync_state())
+}
+
+async fn readyz(State(indexer): State<Arc<BlockIndexer>>) -> Response {
+    let readiness = indexer.readiness().await;
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    json_response(status, &readiness)
+}
+
+fn json_response(status: StatusCode, body: &impl Serialize) -> Response {
+    let body = serde_json::to_string(body).expect("health reports serialize to JSON");
+    (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
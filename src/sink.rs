@@ -0,0 +1,446 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::{join_all, try_join_all};
+use std::collections::HashMap;
+
+use crate::config::{Config, SinkKind};
+use crate::elasticsearch::ElasticsearchClient;
+use crate::models::{FinalityStatus, IndexedBlock};
+use crate::ndjson::NdjsonSink;
+use crate::parquet::ParquetSink;
+use crate::postgres::PostgresSink;
+use crate::progress::SyncProgress;
+use crate::sqlite::SqliteSink;
+
+/// Storage backend for indexed blocks and the sync progress.
+///
+/// Writes must be idempotent: a block written again replaces the stored one, so batches can be
+/// retried and ranges re-indexed.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> String;
+
+    /// Create the indices or tables this sink writes to, if missing
+    async fn create_indices(&self) -> Result<()>;
+
+    /// Whether the backend answers
+    async fn ping(&self) -> bool;
+
+    /// Write blocks, plus their transactions and logs as configured, replacing stored blocks
+    /// with the same number. An error means some of the blocks may not have been written.
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()>;
+
+    /// Delete every stored block above `block_number`, returning how many were deleted
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64>;
+
+    /// Delete the transaction and log documents stored apart from the blocks `block_numbers`,
+    /// before the blocks are written again with different contents. Sinks replacing them
+    /// together with their block keep the default.
+    async fn delete_block_children(&self, _block_numbers: &[u64]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Promote every block up to `block_number` to `status`, leaving blocks that are
+    /// already at or beyond that status untouched. Returns how many blocks were updated.
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64>;
+
+    /// Load the sync progress; a new sink starts from block 0
+    async fn get_progress(&self) -> Result<SyncProgress>;
+
+    /// Persist the sync progress
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()>;
+
+    /// Write blocks together with the progress that accounts for them. Sinks able to commit
+    /// both atomically override this; the default writes the blocks, then the progress, so a
+    /// failure in between only means the blocks are written again.
+    async fn write_blocks_with_progress(
+        &self,
+        blocks: &[IndexedBlock],
+        progress: &SyncProgress,
+    ) -> Result<()> {
+        self.write_blocks(blocks).await?;
+        self.set_progress(progress).await
+    }
+
+    /// Make recent writes visible to the reads below
+    async fn refresh(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the reads below cover every written block. Sinks keeping only recent blocks
+    /// return false and are left out of gap searches and verification.
+    fn keeps_history(&self) -> bool {
+        true
+    }
+
+    /// The sinks this one writes to, for checks that must look at each of them
+    fn members(&self) -> Option<&[Box<dyn Sink>]> {
+        None
+    }
+
+    /// Fetch a stored block by number, or `None` if it has not been indexed
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>>;
+
+    /// Fetch several stored blocks by number; blocks that are not indexed are absent from the map
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>>;
+
+    /// Count stored blocks in `from..=to` per bucket of `interval` block numbers, keyed by the
+    /// first block number of the bucket. Empty buckets are included.
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>>;
+}
+
+/// Connect to the configured sinks and create their indices
+pub async fn connect(config: &Config) -> Result<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::with_capacity(config.sinks.len());
+
+    for kind in &config.sinks {
+        let sink: Box<dyn Sink> = match kind {
+            SinkKind::Elasticsearch => Box::new(ElasticsearchClient::new(config)?),
+            SinkKind::Postgres => Box::new(PostgresSink::connect(config).await?),
+            SinkKind::Sqlite => Box::new(SqliteSink::open(config)?),
+            SinkKind::Parquet => Box::new(ParquetSink::open(config).await?),
+            SinkKind::Ndjson => Box::new(NdjsonSink::open(config)?),
+        };
+        sink.create_indices().await?;
+        log::info!("Connected to {} successfully", sink.name());
+        sinks.push(sink);
+    }
+
+    if sinks.len() == 1 {
+        Ok(sinks.remove(0))
+    } else {
+        Ok(Box::new(FanOutSink::new(sinks)))
+    }
+}
+
+/// Writes to several sinks at once. Reads are served by the first sink, the sync resumes from
+/// the least advanced one so that a sink that fell behind catches up, and gap searches count a
+/// block as missing when any sink keeping history lacks it.
+pub struct FanOutSink {
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+impl FanOutSink {
+    pub fn new(sinks: Vec<Box<dyn Sink>>) -> Self {
+        assert!(!sinks.is_empty(), "a fan-out needs at least one sink");
+        FanOutSink { sinks }
+    }
+
+    fn primary(&self) -> &dyn Sink {
+        self.sinks[0].as_ref()
+    }
+}
+
+#[async_trait]
+impl Sink for FanOutSink {
+    fn name(&self) -> String {
+        let names: Vec<String> = self.sinks.iter().map(|sink| sink.name()).collect();
+        names.join(" + ")
+    }
+
+    async fn create_indices(&self) -> Result<()> {
+        for sink in &self.sinks {
+            sink.create_indices().await?;
+        }
+        Ok(())
+    }
+
+    async fn ping(&self) -> bool {
+        join_all(self.sinks.iter().map(|sink| sink.ping()))
+            .await
+            .into_iter()
+            .all(|reachable| reachable)
+    }
+
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        try_join_all(self.sinks.iter().map(|sink| sink.write_blocks(blocks))).await?;
+        Ok(())
+    }
+
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        let deleted = try_join_all(
+            self.sinks
+                .iter()
+                .map(|sink| sink.delete_blocks_after(block_number)),
+        )
+        .await?;
+        Ok(deleted.into_iter().max().unwrap_or(0))
+    }
+
+    async fn delete_block_children(&self, block_numbers: &[u64]) -> Result<()> {
+        try_join_all(
+            self.sinks
+                .iter()
+                .map(|sink| sink.delete_block_children(block_numbers)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64> {
+        let updated = try_join_all(
+            self.sinks
+                .iter()
+                .map(|sink| sink.update_finality(block_number, status)),
+        )
+        .await?;
+        Ok(updated.into_iter().max().unwrap_or(0))
+    }
+
+    async fn get_progress(&self) -> Result<SyncProgress> {
+        let progress = try_join_all(self.sinks.iter().map(|sink| sink.get_progress())).await?;
+        Ok(progress
+            .into_iter()
+            .min_by_key(SyncProgress::checkpoint)
+            .expect("a fan-out has at least one sink"))
+    }
+
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
+        try_join_all(self.sinks.iter().map(|sink| sink.set_progress(progress))).await?;
+        Ok(())
+    }
+
+    async fn write_blocks_with_progress(
+        &self,
+        blocks: &[IndexedBlock],
+        progress: &SyncProgress,
+    ) -> Result<()> {
+        try_join_all(
+            self.sinks
+                .iter()
+                .map(|sink| sink.write_blocks_with_progress(blocks, progress)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        try_join_all(self.sinks.iter().map(|sink| sink.refresh())).await?;
+        Ok(())
+    }
+
+    fn keeps_history(&self) -> bool {
+        self.sinks.iter().any(|sink| sink.keeps_history())
+    }
+
+    fn members(&self) -> Option<&[Box<dyn Sink>]> {
+        Some(&self.sinks)
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        self.primary().get_block(block_number).await
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        self.primary().get_blocks(block_numbers).await
+    }
+
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        let histograms = try_join_all(
+            self.sinks
+                .iter()
+                .filter(|sink| sink.keeps_history())
+                .map(|sink| sink.block_count_histogram(from, to, interval)),
+        )
+        .await?;
+
+        let mut histograms = histograms.into_iter();
+        let Some(mut merged) = histograms.next() else {
+            return self
+                .primary()
+                .block_count_histogram(from, to, interval)
+                .await;
+        };
+        // A block is only complete once every sink has it
+        for histogram in histograms {
+            let counts: HashMap<u64, u64> = histogram.into_iter().collect();
+            for (bucket, count) in &mut merged {
+                *count = (*count).min(counts.get(bucket).copied().unwrap_or(0));
+            }
+        }
+        Ok(merged)
+    }
+}
+
+/// Sink keeping everything in memory, for tests
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    state: std::sync::Arc<std::sync::Mutex<MemoryState>>,
+}
+
+#[cfg(test)]
+#[derive(Default)]
+struct MemoryState {
+    blocks: std::collections::BTreeMap<u64, IndexedBlock>,
+    progress: Option<SyncProgress>,
+}
+
+#[cfg(test)]
+impl MemorySink {
+    pub fn block_numbers(&self) -> Vec<u64> {
+        self.state.lock().unwrap().blocks.keys().copied().collect()
+    }
+
+    pub fn progress(&self) -> Option<SyncProgress> {
+        self.state.lock().unwrap().progress.clone()
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Sink for MemorySink {
+    fn name(&self) -> String {
+        "memory".to_string()
+    }
+
+    async fn create_indices(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn ping(&self) -> bool {
+        true
+    }
+
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        for block in blocks {
+            state.blocks.insert(block.number, block.clone());
+        }
+        Ok(())
+    }
+
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let removed = state.blocks.split_off(&(block_number + 1));
+        Ok(removed.len() as u64)
+    }
+
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64> {
+        let mut state = self.state.lock().unwrap();
+        let mut updated = 0;
+        for block in state.blocks.range_mut(..=block_number).map(|(_, b)| b) {
+            if block.finality < status {
+                block.finality = status;
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    async fn get_progress(&self) -> Result<SyncProgress> {
+        Ok(self
+            .progress()
+            .unwrap_or_else(|| SyncProgress::from_checkpoint(0)))
+    }
+
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
+        self.state.lock().unwrap().progress = Some(progress.clone());
+        Ok(())
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .blocks
+            .get(&block_number)
+            .cloned())
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        let state = self.state.lock().unwrap();
+        Ok(block_numbers
+            .iter()
+            .filter_map(|n| state.blocks.get(n).map(|block| (*n, block.clone())))
+            .collect())
+    }
+
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        let state = self.state.lock().unwrap();
+        let mut counts = HashMap::new();
+        for number in state.blocks.range(from..=to).map(|(n, _)| n) {
+            *counts.entry(number - number % interval).or_default() += 1;
+        }
+        Ok(crate::gaps::fill_buckets(from, to, interval, &counts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+
+    #[tokio::test]
+    async fn test_fan_out_writes_to_every_sink() {
+        let (a, b) = (MemorySink::default(), MemorySink::default());
+        let fan_out = FanOutSink::new(vec![Box::new(a.clone()), Box::new(b.clone())]);
+        assert_eq!(fan_out.name(), "memory + memory");
+
+        let blocks: Vec<IndexedBlock> = (1..=5).map(test_block).collect();
+        fan_out.write_blocks(&blocks).await.unwrap();
+        assert_eq!(a.block_numbers(), vec![1, 2, 3, 4, 5]);
+        assert_eq!(b.block_numbers(), vec![1, 2, 3, 4, 5]);
+
+        assert_eq!(fan_out.delete_blocks_after(3).await.unwrap(), 2);
+        assert_eq!(b.block_numbers(), vec![1, 2, 3]);
+        assert!(fan_out.get_block(3).await.unwrap().is_some());
+        assert_eq!(
+            fan_out.block_count_histogram(0, 9, 5).await.unwrap(),
+            vec![(0, 3), (5, 0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_reports_what_any_sink_misses() {
+        let (a, b) = (MemorySink::default(), MemorySink::default());
+        let blocks: Vec<IndexedBlock> = (0..10).map(test_block).collect();
+        a.write_blocks(&blocks).await.unwrap();
+        b.write_blocks(&blocks[..7]).await.unwrap();
+
+        let fan_out = FanOutSink::new(vec![Box::new(a.clone()), Box::new(b.clone())]);
+        assert_eq!(
+            fan_out.block_count_histogram(0, 9, 5).await.unwrap(),
+            vec![(0, 5), (5, 2)]
+        );
+        assert_eq!(fan_out.delete_blocks_after(4).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_fan_out_resumes_from_least_advanced_sink() {
+        let (a, b) = (MemorySink::default(), MemorySink::default());
+        a.set_progress(&SyncProgress::from_checkpoint(100))
+            .await
+            .unwrap();
+        b.set_progress(&SyncProgress::from_checkpoint(40))
+            .await
+            .unwrap();
+
+        let fan_out = FanOutSink::new(vec![Box::new(a.clone()), Box::new(b.clone())]);
+        assert_eq!(fan_out.get_progress().await.unwrap().checkpoint(), 40);
+
+        fan_out
+            .set_progress(&SyncProgress::from_checkpoint(120))
+            .await
+            .unwrap();
+        assert_eq!(a.progress().unwrap().checkpoint(), 120);
+        assert_eq!(b.progress().unwrap().checkpoint(), 120);
+    }
+}
@@ -0,0 +1,451 @@
+//! Relational layout of indexed blocks shared by the SQL sinks.
+//!
+//! Rows are exchanged as text, one optional string per column, and each sink maps the
+//! column types to its own SQL types. Wei amounts keep their full precision as decimals.
+
+use anyhow::{Context, Result};
+use std::str::FromStr;
+
+use crate::models::{FinalityStatus, IndexedBlock, IndexedLog, IndexedTransaction};
+
+/// Type of a column, mapped to a concrete SQL type by each sink
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Integer,
+    /// Arbitrary precision decimal, for wei amounts
+    Numeric,
+    Text,
+    Boolean,
+    /// JSON document, for nested lists
+    Json,
+}
+
+pub struct Column {
+    pub name: &'static str,
+    pub kind: ColumnType,
+}
+
+const fn column(name: &'static str, kind: ColumnType) -> Column {
+    Column { name, kind }
+}
+
+/// A table of the relational layout
+pub struct Table {
+    /// Appended to the table prefix, e.g. `blocks`
+    pub suffix: &'static str,
+    pub columns: &'static [Column],
+    /// Primary key columns
+    pub key: &'static [&'static str],
+    /// Columns indexed for lookups
+    pub indexes: &'static [&'static str],
+    /// Whether rows belong to a block through a `block_number` column, and are deleted with it
+    pub per_block: bool,
+}
+
+impl Table {
+    pub fn column_names(&self) -> Vec<&'static str> {
+        self.columns.iter().map(|c| c.name).collect()
+    }
+}
+
+/// A row as the text of each column, `None` being NULL
+pub type Row = Vec<Option<String>>;
+
+pub const BLOCKS: Table = Table {
+    suffix: "blocks",
+    columns: &[
+        column("number", ColumnType::Integer),
+        column("hash", ColumnType::Text),
+        column("parent_hash", ColumnType::Text),
+        column("timestamp", ColumnType::Integer),
+        column("gas_limit", ColumnType::Integer),
+        column("gas_used", ColumnType::Integer),
+        column("miner", ColumnType::Text),
+        column("difficulty", ColumnType::Numeric),
+        column("total_difficulty", ColumnType::Numeric),
+        column("size", ColumnType::Integer),
+        column("transaction_count", ColumnType::Integer),
+        column("uncles", ColumnType::Integer),
+        column("indexed_at", ColumnType::Integer),
+        column("finality", ColumnType::Text),
+        column("base_fee_per_gas", ColumnType::Numeric),
+        column("withdrawals_root", ColumnType::Text),
+        column("blob_gas_used", ColumnType::Integer),
+        column("excess_blob_gas", ColumnType::Integer),
+        column("withdrawals", ColumnType::Json),
+    ],
+    key: &["number"],
+    indexes: &["hash", "timestamp"],
+    per_block: false,
+};
+
+pub const TRANSACTIONS: Table = Table {
+    suffix: "transactions",
+    columns: &[
+        column("hash", ColumnType::Text),
+        column("block_number", ColumnType::Integer),
+        column("transaction_index", ColumnType::Integer),
+        column("from_address", ColumnType::Text),
+        column("to_address", ColumnType::Text),
+        column("value", ColumnType::Numeric),
+        column("gas", ColumnType::Integer),
+        column("gas_price", ColumnType::Numeric),
+        column("input", ColumnType::Text),
+        column("nonce", ColumnType::Integer),
+        column("status", ColumnType::Integer),
+        column("gas_used", ColumnType::Integer),
+        column("cumulative_gas_used", ColumnType::Integer),
+        column("effective_gas_price", ColumnType::Numeric),
+        column("contract_address", ColumnType::Text),
+        column("logs_bloom", ColumnType::Text),
+        column("transaction_type", ColumnType::Integer),
+        column("max_fee_per_gas", ColumnType::Numeric),
+        column("max_priority_fee_per_gas", ColumnType::Numeric),
+        column("max_fee_per_blob_gas", ColumnType::Numeric),
+        column("access_list", ColumnType::Json),
+        column("chain_id", ColumnType::Integer),
+        column("blob_versioned_hashes", ColumnType::Json),
+    ],
+    key: &["hash"],
+    indexes: &[
+        "block_number",
+        "from_address",
+        "to_address",
+        "contract_address",
+    ],
+    per_block: true,
+};
+
+pub const LOGS: Table = Table {
+    suffix: "logs",
+    columns: &[
+        column("block_number", ColumnType::Integer),
+        column("log_index", ColumnType::Integer),
+        column("transaction_hash", ColumnType::Text),
+        column("transaction_index", ColumnType::Integer),
+        column("address", ColumnType::Text),
+        column("topic0", ColumnType::Text),
+        column("topic1", ColumnType::Text),
+        column("topic2", ColumnType::Text),
+        column("topic3", ColumnType::Text),
+        column("data", ColumnType::Text),
+        column("removed", ColumnType::Boolean),
+    ],
+    key: &["block_number", "log_index"],
+    indexes: &["address", "topic0", "transaction_hash"],
+    per_block: true,
+};
+
+/// Quote an identifier; table names come from `INDEX_PREFIX`
+pub fn ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// `CREATE TABLE` and `CREATE INDEX` statements for `table`, with column types given by `sql_type`
+pub fn create_table_sql(
+    table: &Table,
+    name: &str,
+    blocks_table: &str,
+    sql_type: fn(ColumnType) -> &'static str,
+) -> String {
+    let mut definitions: Vec<String> = table
+        .columns
+        .iter()
+        .map(|column| format!("{} {}", column.name, sql_type(column.kind)))
+        .collect();
+    definitions.push(format!("PRIMARY KEY ({})", table.key.join(", ")));
+    if table.per_block {
+        definitions.push(format!(
+            "FOREIGN KEY (block_number) REFERENCES {} (number) ON DELETE CASCADE",
+            ident(blocks_table)
+        ));
+    }
+
+    let mut statements = vec![format!(
+        "CREATE TABLE IF NOT EXISTS {} ({})",
+        ident(name),
+        definitions.join(", ")
+    )];
+    for column in table.indexes {
+        statements.push(format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+            ident(&format!("{}_{}_idx", name, column)),
+            ident(name),
+            column
+        ));
+    }
+    statements.join(";\n")
+}
+
+fn json(value: &impl serde::Serialize) -> Option<String> {
+    Some(serde_json::to_string(value).expect("models serialize to JSON"))
+}
+
+pub fn block_row(block: &IndexedBlock) -> Row {
+    vec![
+        Some(block.number.to_string()),
+        Some(block.hash.clone()),
+        Some(block.parent_hash.clone()),
+        Some(block.timestamp.to_string()),
+        Some(block.gas_limit.to_string()),
+        Some(block.gas_used.to_string()),
+        block.miner.clone(),
+        Some(block.difficulty.clone()),
+        Some(block.total_difficulty.clone()),
+        Some(block.size.to_string()),
+        Some(block.transaction_count.to_string()),
+        Some(block.uncles.to_string()),
+        Some(block.indexed_at.to_string()),
+        Some(block.finality.as_str().to_string()),
+        block.base_fee_per_gas.clone(),
+        block.withdrawals_root.clone(),
+        block.blob_gas_used.map(|g| g.to_string()),
+        block.excess_blob_gas.map(|g| g.to_string()),
+        json(&block.withdrawals),
+    ]
+}
+
+pub fn transaction_rows(block: &IndexedBlock) -> Vec<Row> {
+    block
+        .transactions
+        .iter()
+        .map(|tx| {
+            vec![
+                Some(tx.hash.clone()),
+                Some(block.number.to_string()),
+                tx.transaction_index.map(|i| i.to_string()),
+                Some(tx.from.clone()),
+                tx.to.clone(),
+                Some(tx.value.clone()),
+                Some(tx.gas.to_string()),
+                Some(tx.gas_price.clone()),
+                Some(tx.input.clone()),
+                Some(tx.nonce.to_string()),
+                tx.status.map(|s| s.to_string()),
+                tx.gas_used.map(|g| g.to_string()),
+                tx.cumulative_gas_used.map(|g| g.to_string()),
+                tx.effective_gas_price.clone(),
+                tx.contract_address.clone(),
+                tx.logs_bloom.clone(),
+                tx.transaction_type.map(|t| t.to_string()),
+                tx.max_fee_per_gas.clone(),
+                tx.max_priority_fee_per_gas.clone(),
+                tx.max_fee_per_blob_gas.clone(),
+                tx.access_list.as_ref().and_then(json),
+                tx.chain_id.map(|c| c.to_string()),
+                tx.blob_versioned_hashes.as_ref().and_then(json),
+            ]
+        })
+        .collect()
+}
+
+pub fn log_rows(block: &IndexedBlock) -> Vec<Row> {
+    block
+        .logs
+        .iter()
+        .map(|log| {
+            vec![
+                Some(log.block_number.to_string()),
+                Some(log.log_index.to_string()),
+                Some(log.transaction_hash.clone()),
+                Some(log.transaction_index.to_string()),
+                Some(log.address.clone()),
+                log.topic0.clone(),
+                log.topic1.clone(),
+                log.topic2.clone(),
+                log.topic3.clone(),
+                Some(log.data.clone()),
+                Some(log.removed.to_string()),
+            ]
+        })
+        .collect()
+}
+
+/// Reads the columns of a row in table order
+struct Fields<'a> {
+    table: &'a Table,
+    values: std::vec::IntoIter<Option<String>>,
+    position: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn new(table: &'a Table, row: Row) -> Self {
+        Fields {
+            table,
+            values: row.into_iter(),
+            position: 0,
+        }
+    }
+
+    fn optional(&mut self) -> Option<String> {
+        self.position += 1;
+        self.values.next().flatten()
+    }
+
+    fn required(&mut self) -> Result<String> {
+        let name = self.table.columns[self.position].name;
+        self.optional()
+            .with_context(|| format!("{}.{} is NULL", self.table.suffix, name))
+    }
+
+    fn parse<T>(&mut self) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let name = self.table.columns[self.position].name;
+        self.required()?
+            .parse()
+            .with_context(|| format!("Invalid {}.{}", self.table.suffix, name))
+    }
+
+    fn parse_optional<T>(&mut self) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        let name = self.table.columns[self.position].name;
+        self.optional()
+            .map(|value| value.parse())
+            .transpose()
+            .with_context(|| format!("Invalid {}.{}", self.table.suffix, name))
+    }
+
+    fn json<T: serde::de::DeserializeOwned>(&mut self) -> Result<Option<T>> {
+        let name = self.table.columns[self.position].name;
+        self.optional()
+            .map(|value| serde_json::from_str(&value))
+            .transpose()
+            .with_context(|| format!("Invalid {}.{}", self.table.suffix, name))
+    }
+}
+
+/// Rebuild a block from a row of `BLOCKS`, without its transactions and logs
+pub fn block_from_row(row: Row) -> Result<IndexedBlock> {
+    let mut f = Fields::new(&BLOCKS, row);
+    Ok(IndexedBlock {
+        number: f.parse()?,
+        hash: f.required()?,
+        parent_hash: f.required()?,
+        timestamp: f.parse()?,
+        gas_limit: f.parse()?,
+        gas_used: f.parse()?,
+        miner: f.optional(),
+        difficulty: f.required()?,
+        total_difficulty: f.required()?,
+        size: f.parse()?,
+        transactions: Vec::new(),
+        transaction_count: f.parse()?,
+        uncles: f.parse()?,
+        indexed_at: f.parse()?,
+        finality: match f.required()?.as_str() {
+            "safe" => FinalityStatus::Safe,
+            "finalized" => FinalityStatus::Finalized,
+            _ => FinalityStatus::Unfinalized,
+        },
+        base_fee_per_gas: f.optional(),
+        withdrawals_root: f.optional(),
+        blob_gas_used: f.parse_optional()?,
+        excess_blob_gas: f.parse_optional()?,
+        withdrawals: f.json()?.unwrap_or_default(),
+        logs: Vec::new(),
+    })
+}
+
+/// Rebuild a transaction from a row of `TRANSACTIONS`, with the number of its block
+pub fn transaction_from_row(row: Row) -> Result<(u64, IndexedTransaction)> {
+    let mut f = Fields::new(&TRANSACTIONS, row);
+    let hash = f.required()?;
+    let block_number = f.parse()?;
+    let transaction = IndexedTransaction {
+        hash,
+        transaction_index: f.parse_optional()?,
+        from: f.required()?,
+        to: f.optional(),
+        value: f.required()?,
+        gas: f.parse()?,
+        gas_price: f.required()?,
+        input: f.required()?,
+        nonce: f.parse()?,
+        status: f.parse_optional()?,
+        gas_used: f.parse_optional()?,
+        cumulative_gas_used: f.parse_optional()?,
+        effective_gas_price: f.optional(),
+        contract_address: f.optional(),
+        logs_bloom: f.optional(),
+        transaction_type: f.parse_optional()?,
+        max_fee_per_gas: f.optional(),
+        max_priority_fee_per_gas: f.optional(),
+        max_fee_per_blob_gas: f.optional(),
+        access_list: f.json()?,
+        chain_id: f.parse_optional()?,
+        blob_versioned_hashes: f.json()?,
+    };
+    Ok((block_number, transaction))
+}
+
+/// Rebuild a log from a row of `LOGS`. The block hash and timestamp are not stored with logs
+/// and are left empty for the caller to copy from the block.
+pub fn log_from_row(row: Row) -> Result<IndexedLog> {
+    let mut f = Fields::new(&LOGS, row);
+    Ok(IndexedLog {
+        block_number: f.parse()?,
+        log_index: f.parse()?,
+        transaction_hash: f.required()?,
+        transaction_index: f.parse()?,
+        address: f.required()?,
+        topic0: f.optional(),
+        topic1: f.optional(),
+        topic2: f.optional(),
+        topic3: f.optional(),
+        data: f.required()?,
+        removed: f.parse()?,
+        block_hash: String::new(),
+        timestamp: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+
+    #[test]
+    fn test_rows_match_columns() {
+        let block = test_block(7);
+        assert_eq!(block_row(&block).len(), BLOCKS.columns.len());
+        assert_eq!(
+            transaction_rows(&block)[0].len(),
+            TRANSACTIONS.columns.len()
+        );
+        assert_eq!(log_rows(&block)[0].len(), LOGS.columns.len());
+    }
+
+    #[test]
+    fn test_block_round_trip() {
+        let mut block = test_block(7);
+        block.finality = FinalityStatus::Safe;
+
+        let mut stored = block_from_row(block_row(&block)).unwrap();
+        for row in transaction_rows(&block) {
+            let (block_number, tx) = transaction_from_row(row).unwrap();
+            assert_eq!(block_number, 7);
+            stored.transactions.push(tx);
+        }
+        for row in log_rows(&block) {
+            let mut log = log_from_row(row).unwrap();
+            log.block_hash = stored.hash.clone();
+            log.timestamp = stored.timestamp;
+            stored.logs.push(log);
+        }
+
+        assert_eq!(
+            serde_json::to_value(&stored).unwrap(),
+            serde_json::to_value(&block).unwrap()
+        );
+        assert_eq!(
+            serde_json::to_value(&stored.logs).unwrap(),
+            serde_json::to_value(&block.logs).unwrap()
+        );
+    }
+}
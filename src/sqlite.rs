@@ -0,0 +1,517 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params_from_iter, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::gaps;
+use crate::models::{FinalityStatus, IndexedBlock};
+use crate::progress::SyncProgress;
+use crate::sink::Sink;
+use crate::sql::{self, ident, ColumnType, Row, Table};
+
+/// Stores blocks, transactions and logs in a single SQLite database file.
+///
+/// A single connection in WAL mode does all the writing, so other processes can query the file
+/// while the indexer runs. Like the postgres sink, live sync commits each block together with
+/// the checkpoint covering it, while backfill commits the checkpoint after the batch.
+pub struct SqliteSink {
+    connection: Arc<Mutex<Connection>>,
+    tables: Arc<Tables>,
+}
+
+struct Tables {
+    blocks: String,
+    transactions: String,
+    logs: String,
+    progress: String,
+    index_logs: bool,
+}
+
+fn sql_type(kind: ColumnType) -> &'static str {
+    match kind {
+        ColumnType::Integer | ColumnType::Boolean => "INTEGER",
+        // Wei amounts exceed 64 bits, so decimals are kept as text
+        ColumnType::Numeric | ColumnType::Text | ColumnType::Json => "TEXT",
+    }
+}
+
+fn to_value(kind: ColumnType, text: Option<String>) -> Value {
+    match (kind, text) {
+        (_, None) => Value::Null,
+        (ColumnType::Integer, Some(text)) => text
+            .parse()
+            .map(Value::Integer)
+            .unwrap_or(Value::Text(text)),
+        (ColumnType::Boolean, Some(text)) => Value::Integer((text == "true") as i64),
+        (_, Some(text)) => Value::Text(text),
+    }
+}
+
+fn to_text(kind: ColumnType, value: ValueRef<'_>) -> Option<String> {
+    match value {
+        ValueRef::Null => None,
+        ValueRef::Integer(i) if kind == ColumnType::Boolean => Some((i != 0).to_string()),
+        ValueRef::Integer(i) => Some(i.to_string()),
+        ValueRef::Real(f) => Some(f.to_string()),
+        ValueRef::Text(bytes) | ValueRef::Blob(bytes) => {
+            Some(String::from_utf8_lossy(bytes).into_owned())
+        }
+    }
+}
+
+fn read_row(table: &Table, row: &rusqlite::Row<'_>) -> rusqlite::Result<Row> {
+    table
+        .columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| Ok(to_text(column.kind, row.get_ref(i)?)))
+        .collect()
+}
+
+fn insert_rows(connection: &Connection, table: &Table, name: &str, rows: Vec<Row>) -> Result<()> {
+    let mut statement = connection.prepare_cached(&format!(
+        "INSERT OR REPLACE INTO {} ({}) VALUES ({})",
+        ident(name),
+        table.column_names().join(", "),
+        vec!["?"; table.columns.len()].join(", ")
+    ))?;
+    for row in rows {
+        let values = table
+            .columns
+            .iter()
+            .zip(row)
+            .map(|(column, text)| to_value(column.kind, text));
+        statement.execute(params_from_iter(values))?;
+    }
+    Ok(())
+}
+
+impl Tables {
+    /// Write blocks and, when given, the progress in a single transaction
+    fn write(
+        &self,
+        connection: &mut Connection,
+        blocks: &[IndexedBlock],
+        progress: Option<&SyncProgress>,
+    ) -> Result<()> {
+        let tx = connection.transaction()?;
+
+        // Deleting stored copies takes their transactions and logs along
+        {
+            let mut delete = tx.prepare_cached(&format!(
+                "DELETE FROM {} WHERE number = ?1",
+                ident(&self.blocks)
+            ))?;
+            for block in blocks {
+                delete.execute([block.number as i64])?;
+            }
+        }
+
+        insert_rows(
+            &tx,
+            &sql::BLOCKS,
+            &self.blocks,
+            blocks.iter().map(sql::block_row).collect(),
+        )?;
+        insert_rows(
+            &tx,
+            &sql::TRANSACTIONS,
+            &self.transactions,
+            blocks.iter().flat_map(sql::transaction_rows).collect(),
+        )?;
+        if self.index_logs {
+            insert_rows(
+                &tx,
+                &sql::LOGS,
+                &self.logs,
+                blocks.iter().flat_map(sql::log_rows).collect(),
+            )?;
+        }
+
+        if let Some(progress) = progress {
+            self.save_progress(&tx, progress)?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn save_progress(&self, connection: &Connection, progress: &SyncProgress) -> Result<()> {
+        connection.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} (id, scanned_through, checkpoint, failed_blocks, \
+                 updated_at) VALUES (1, ?1, ?2, ?3, ?4)",
+                ident(&self.progress)
+            ),
+            (
+                progress.scanned_through as i64,
+                progress.checkpoint() as i64,
+                serde_json::to_string(&progress.failed_blocks)?,
+                chrono::Utc::now().timestamp(),
+            ),
+        )?;
+        Ok(())
+    }
+
+    fn get_blocks(
+        &self,
+        connection: &Connection,
+        block_numbers: &[u64],
+    ) -> Result<HashMap<u64, IndexedBlock>> {
+        let mut select_block = connection.prepare_cached(&format!(
+            "SELECT {} FROM {} WHERE number = ?1",
+            sql::BLOCKS.column_names().join(", "),
+            ident(&self.blocks)
+        ))?;
+        let mut select_transactions = connection.prepare_cached(&format!(
+            "SELECT {} FROM {} WHERE block_number = ?1 ORDER BY transaction_index",
+            sql::TRANSACTIONS.column_names().join(", "),
+            ident(&self.transactions)
+        ))?;
+
+        let mut blocks = HashMap::with_capacity(block_numbers.len());
+        for &number in block_numbers {
+            let row = select_block
+                .query_row([number as i64], |row| read_row(&sql::BLOCKS, row))
+                .optional()?;
+            let Some(row) = row else {
+                continue;
+            };
+
+            let mut block = sql::block_from_row(row)?;
+            let rows = select_transactions
+                .query_map([number as i64], |row| read_row(&sql::TRANSACTIONS, row))?;
+            for row in rows {
+                let (_, transaction) = sql::transaction_from_row(row?)?;
+                block.transactions.push(transaction);
+            }
+            blocks.insert(number, block);
+        }
+        Ok(blocks)
+    }
+}
+
+impl SqliteSink {
+    pub fn open(config: &Config) -> Result<Self> {
+        let path = &config.sqlite_path;
+        let connection = Connection::open(path)
+            .with_context(|| format!("Failed to open SQLite database {}", path.display()))?;
+
+        let journal_mode: String =
+            connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+        if !journal_mode.eq_ignore_ascii_case("wal") {
+            log::warn!(
+                "SQLite database {} uses journal mode {} instead of WAL",
+                path.display(),
+                journal_mode
+            );
+        }
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.pragma_update(None, "foreign_keys", "ON")?;
+        connection.busy_timeout(Duration::from_secs(5))?;
+
+        Ok(SqliteSink {
+            connection: Arc::new(Mutex::new(connection)),
+            tables: Arc::new(Tables {
+                blocks: config.table_name(sql::BLOCKS.suffix),
+                transactions: config.table_name(sql::TRANSACTIONS.suffix),
+                logs: config.table_name(sql::LOGS.suffix),
+                progress: config.table_name("progress"),
+                index_logs: config.index_receipts && config.index_logs,
+            }),
+        })
+    }
+
+    /// Run `f` with the connection on the blocking thread pool
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection, &Tables) -> Result<T> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        let tables = Arc::clone(&self.tables);
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection.lock().expect("SQLite connection poisoned");
+            f(&mut connection, &tables)
+        })
+        .await
+        .context("SQLite task panicked")?
+    }
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    fn name(&self) -> String {
+        "SQLite".to_string()
+    }
+
+    async fn create_indices(&self) -> Result<()> {
+        self.run(|connection, tables| {
+            let mut definitions = vec![
+                (&sql::BLOCKS, &tables.blocks),
+                (&sql::TRANSACTIONS, &tables.transactions),
+            ];
+            if tables.index_logs {
+                definitions.push((&sql::LOGS, &tables.logs));
+            }
+            let mut statements: Vec<String> = definitions
+                .into_iter()
+                .map(|(table, name)| sql::create_table_sql(table, name, &tables.blocks, sql_type))
+                .collect();
+            statements.push(format!(
+                "CREATE TABLE IF NOT EXISTS {} (\
+                 id INTEGER PRIMARY KEY CHECK (id = 1), \
+                 scanned_through INTEGER NOT NULL, \
+                 checkpoint INTEGER NOT NULL, \
+                 failed_blocks TEXT NOT NULL, \
+                 updated_at INTEGER NOT NULL)",
+                ident(&tables.progress)
+            ));
+
+            let tx = connection.transaction()?;
+            tx.execute_batch(&statements.join(";\n"))?;
+            tx.commit()?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ping(&self) -> bool {
+        // Waiting for a busy connection would park a blocking thread per check, so a
+        // connection held by a write is reported as unreachable
+        match self.connection.try_lock() {
+            Ok(connection) => connection.execute_batch("SELECT 1").is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    async fn write_blocks(&self, blocks: &[IndexedBlock]) -> Result<()> {
+        let blocks = blocks.to_vec();
+        self.run(move |connection, tables| tables.write(connection, &blocks, None))
+            .await
+    }
+
+    async fn write_blocks_with_progress(
+        &self,
+        blocks: &[IndexedBlock],
+        progress: &SyncProgress,
+    ) -> Result<()> {
+        let blocks = blocks.to_vec();
+        let progress = progress.clone();
+        self.run(move |connection, tables| tables.write(connection, &blocks, Some(&progress)))
+            .await
+    }
+
+    async fn delete_blocks_after(&self, block_number: u64) -> Result<u64> {
+        let deleted = self
+            .run(move |connection, tables| {
+                Ok(connection.execute(
+                    &format!("DELETE FROM {} WHERE number > ?1", ident(&tables.blocks)),
+                    [block_number as i64],
+                )?)
+            })
+            .await?;
+        log::debug!(
+            "Deleted {} block(s) above {} from SQLite",
+            deleted,
+            block_number
+        );
+        Ok(deleted as u64)
+    }
+
+    async fn update_finality(&self, block_number: u64, status: FinalityStatus) -> Result<u64> {
+        let settled: &[&str] = match status {
+            FinalityStatus::Unfinalized => return Ok(0),
+            FinalityStatus::Safe => &["safe", "finalized"],
+            FinalityStatus::Finalized => &["finalized"],
+        };
+
+        let updated = self
+            .run(move |connection, tables| {
+                let mut params = vec![
+                    Value::Text(status.as_str().to_string()),
+                    Value::Integer(block_number as i64),
+                ];
+                params.extend(settled.iter().map(|s| Value::Text(s.to_string())));
+                Ok(connection.execute(
+                    &format!(
+                        "UPDATE {} SET finality = ? WHERE number <= ? AND finality NOT IN ({})",
+                        ident(&tables.blocks),
+                        vec!["?"; settled.len()].join(", ")
+                    ),
+                    params_from_iter(params),
+                )?)
+            })
+            .await?;
+        Ok(updated as u64)
+    }
+
+    async fn get_progress(&self) -> Result<SyncProgress> {
+        self.run(|connection, tables| {
+            let row = connection
+                .query_row(
+                    &format!(
+                        "SELECT scanned_through, failed_blocks FROM {} WHERE id = 1",
+                        ident(&tables.progress)
+                    ),
+                    [],
+                    |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+                )
+                .optional()?;
+
+            Ok(match row {
+                Some((scanned_through, failed_blocks)) => SyncProgress {
+                    scanned_through: scanned_through as u64,
+                    failed_blocks: serde_json::from_str(&failed_blocks)
+                        .context("Invalid failed_blocks in the SQLite progress")?,
+                },
+                None => SyncProgress::default(),
+            })
+        })
+        .await
+    }
+
+    async fn set_progress(&self, progress: &SyncProgress) -> Result<()> {
+        let progress = progress.clone();
+        self.run(move |connection, tables| tables.save_progress(connection, &progress))
+            .await
+    }
+
+    async fn get_block(&self, block_number: u64) -> Result<Option<IndexedBlock>> {
+        Ok(self
+            .get_blocks(&[block_number])
+            .await?
+            .remove(&block_number))
+    }
+
+    async fn get_blocks(&self, block_numbers: &[u64]) -> Result<HashMap<u64, IndexedBlock>> {
+        let block_numbers = block_numbers.to_vec();
+        self.run(move |connection, tables| tables.get_blocks(connection, &block_numbers))
+            .await
+    }
+
+    async fn block_count_histogram(
+        &self,
+        from: u64,
+        to: u64,
+        interval: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        let counts = self
+            .run(move |connection, tables| {
+                let mut statement = connection.prepare(&format!(
+                    "SELECT number / ?3 * ?3, count(*) FROM {} \
+                     WHERE number BETWEEN ?1 AND ?2 GROUP BY 1",
+                    ident(&tables.blocks)
+                ))?;
+                let rows = statement
+                    .query_map([from as i64, to as i64, interval as i64], |row| {
+                        Ok((row.get::<_, i64>(0)? as u64, row.get::<_, i64>(1)? as u64))
+                    })?;
+                Ok(rows.collect::<rusqlite::Result<HashMap<u64, u64>>>()?)
+            })
+            .await?;
+        Ok(gaps::fill_buckets(from, to, interval, &counts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::test_block;
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("indexer.db");
+        let config =
+            Config::for_test(&[("SINKS", "sqlite"), ("SQLITE_PATH", path.to_str().unwrap())]);
+        let sink = SqliteSink::open(&config).unwrap();
+        sink.create_indices().await.unwrap();
+        assert!(sink.ping().await);
+
+        let blocks: Vec<IndexedBlock> = (0..40).map(test_block).collect();
+        sink.write_blocks(&blocks).await.unwrap();
+        let mut progress = SyncProgress::default();
+        progress.record(40, &[7]);
+        let mut block = test_block(40);
+        block.hash = "0xreplaced".to_string();
+        sink.write_blocks_with_progress(&[block], &progress)
+            .await
+            .unwrap();
+        sink.write_blocks(&[test_block(40)]).await.unwrap();
+
+        assert_eq!(sink.get_progress().await.unwrap(), progress);
+        let stored = sink.get_block(40).await.unwrap().unwrap();
+        assert_eq!(
+            serde_json::to_value(&stored).unwrap(),
+            serde_json::to_value(test_block(40)).unwrap()
+        );
+
+        assert_eq!(
+            sink.update_finality(10, FinalityStatus::Finalized)
+                .await
+                .unwrap(),
+            11
+        );
+        assert_eq!(
+            sink.update_finality(20, FinalityStatus::Safe)
+                .await
+                .unwrap(),
+            10
+        );
+        assert_eq!(
+            sink.get_block(15).await.unwrap().unwrap().finality,
+            FinalityStatus::Safe
+        );
+        assert_eq!(sink.delete_blocks_after(29).await.unwrap(), 11);
+        assert_eq!(
+            sink.block_count_histogram(0, 49, 25).await.unwrap(),
+            vec![(0, 25), (25, 5)]
+        );
+
+        // Transactions and logs went with their blocks, and replaced blocks left no duplicates
+        let connection = Connection::open(&path).unwrap();
+        let count = |table: &str| -> i64 {
+            connection
+                .query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap()
+        };
+        assert_eq!(count("workqueue_transactions"), 30);
+        assert_eq!(count("workqueue_logs"), 30);
+        let journal_mode: String = connection
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+    }
+
+    #[tokio::test]
+    async fn test_busy_connection_is_not_ready() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("indexer.db");
+        let config =
+            Config::for_test(&[("SINKS", "sqlite"), ("SQLITE_PATH", path.to_str().unwrap())]);
+        let sink = SqliteSink::open(&config).unwrap();
+
+        // A write holding the connection
+        let (held, is_held) = tokio::sync::oneshot::channel();
+        let (release, released) = std::sync::mpsc::channel::<()>();
+        let connection = Arc::clone(&sink.connection);
+        let writer = tokio::task::spawn_blocking(move || {
+            let _connection = connection.lock().unwrap();
+            held.send(()).unwrap();
+            released.recv().ok();
+        });
+        is_held.await.unwrap();
+        assert!(!sink.ping().await);
+
+        release.send(()).unwrap();
+        writer.await.unwrap();
+        assert!(sink.ping().await);
+    }
+}
@@ -0,0 +1,349 @@
+use log::{debug, warn};
+use serde_json::Value;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use tokio::time::sleep;
+
+use crate::metrics::METRICS;
+
+/// Weight of the newest sample in the latency and error rate moving averages
+const EWMA_ALPHA: f64 = 0.1;
+
+/// Share of the concurrency limit kept after an overload signal
+const DECREASE_FACTOR: f64 = 0.5;
+
+/// Overload signals within this window of a decrease belong to the same episode
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(2);
+
+/// Requests slower than this multiple of the average latency do not grow the limit
+const LATENCY_TOLERANCE: f64 = 2.0;
+
+/// Error rate above which the limit stops growing
+const HEALTHY_ERROR_RATE: f64 = 0.05;
+
+/// Cost of the RPC methods relative to `eth_blockNumber`, after the compute units providers
+/// bill them at. Methods not listed cost 1.
+const METHOD_COSTS: &[(&str, f64)] = &[
+    ("eth_blockNumber", 1.0),
+    ("eth_chainId", 1.0),
+    ("eth_getBlockByNumber", 2.0),
+    ("eth_getTransactionReceipt", 2.0),
+    ("eth_getLogs", 8.0),
+    ("eth_getBlockReceipts", 50.0),
+];
+
+/// Cost of `eth_getBlockByNumber` when it returns full transaction objects
+const FULL_BLOCK_COST: f64 = 4.0;
+
+/// Tokens charged for one call of `method` with `params`
+pub fn request_cost(method: &str, params: &Value) -> f64 {
+    if method == "eth_getBlockByNumber" && params.get(1) == Some(&Value::Bool(true)) {
+        return FULL_BLOCK_COST;
+    }
+    METHOD_COSTS
+        .iter()
+        .find(|(name, _)| *name == method)
+        .map_or(1.0, |(_, cost)| *cost)
+}
+
+/// Token bucket limiting the request cost per second of one RPC endpoint
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    /// Bucket refilling `rate` tokens per second and holding up to one second worth of them
+    pub fn new(rate: f64) -> Self {
+        let capacity = rate.max(1.0);
+        TokenBucket {
+            rate,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    /// Wait until calls costing `cost` tokens may be sent, e.g. the calls of a JSON-RPC batch
+    pub async fn acquire(&self, cost: f64) {
+        let wait = self.reserve(Instant::now(), cost);
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+
+    /// Take `cost` tokens, returning how long the caller has to wait before they become valid.
+    /// Tokens may go negative so concurrent callers queue up instead of racing for refills.
+    fn reserve(&self, now: Instant, cost: f64) -> Duration {
+        let mut state = self.state.lock().unwrap();
+
+        let elapsed = now.saturating_duration_since(state.updated).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.rate).min(self.capacity);
+        state.updated = state.updated.max(now);
+        state.tokens -= cost;
+
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+}
+
+/// Outcome of one RPC request, fed back into the concurrency controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RequestOutcome {
+    Success(Duration),
+    /// Rate limited (HTTP 429 or equivalent) or timed out
+    Overloaded,
+    Failed,
+}
+
+/// AIMD concurrency limit: grows by one slot per limit's worth of healthy requests and
+/// halves when the endpoints report overload
+#[derive(Debug)]
+pub struct AdaptiveConcurrency {
+    min: usize,
+    max: usize,
+    state: Mutex<AimdState>,
+    released: Notify,
+}
+
+#[derive(Debug)]
+struct AimdState {
+    limit: f64,
+    in_flight: usize,
+    latency_ms: Option<f64>,
+    error_rate: f64,
+    last_decrease: Option<Instant>,
+}
+
+/// Slot held while a block is being fetched; released on drop
+pub struct ConcurrencyPermit<'a> {
+    controller: &'a AdaptiveConcurrency,
+}
+
+impl Drop for ConcurrencyPermit<'_> {
+    fn drop(&mut self) {
+        self.controller.state.lock().unwrap().in_flight -= 1;
+        self.controller.released.notify_waiters();
+    }
+}
+
+impl AdaptiveConcurrency {
+    pub fn new(initial: usize, min: usize, max: usize) -> Self {
+        let min = min.max(1);
+        let max = max.max(min);
+        let initial = initial.clamp(min, max);
+        METRICS.rpc_concurrency_limit.set(initial as i64);
+        AdaptiveConcurrency {
+            min,
+            max,
+            state: Mutex::new(AimdState {
+                limit: initial as f64,
+                in_flight: 0,
+                latency_ms: None,
+                error_rate: 0.0,
+                last_decrease: None,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Current concurrency limit
+    pub fn limit(&self) -> usize {
+        self.state.lock().unwrap().limit as usize
+    }
+
+    /// Wait for a free slot under the current limit
+    pub async fn acquire(&self) -> ConcurrencyPermit<'_> {
+        loop {
+            // Registered before checking so a release in between is not missed
+            let released = self.released.notified();
+            if let Some(permit) = self.try_acquire() {
+                return permit;
+            }
+            released.await;
+        }
+    }
+
+    pub fn try_acquire(&self) -> Option<ConcurrencyPermit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        if state.in_flight >= state.limit as usize {
+            return None;
+        }
+        state.in_flight += 1;
+        Some(ConcurrencyPermit { controller: self })
+    }
+
+    pub fn record(&self, outcome: RequestOutcome) {
+        self.record_at(outcome, Instant::now());
+    }
+
+    fn record_at(&self, outcome: RequestOutcome, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let before = state.limit as usize;
+
+        match outcome {
+            RequestOutcome::Success(latency) => {
+                let latency_ms = latency.as_secs_f64() * 1000.0;
+                let fast = state
+                    .latency_ms
+                    .is_none_or(|avg| latency_ms <= avg * LATENCY_TOLERANCE);
+                state.latency_ms = Some(match state.latency_ms {
+                    Some(avg) => avg + EWMA_ALPHA * (latency_ms - avg),
+                    None => latency_ms,
+                });
+                state.error_rate -= EWMA_ALPHA * state.error_rate;
+
+                if fast && state.error_rate < HEALTHY_ERROR_RATE {
+                    state.limit = (state.limit + 1.0 / state.limit).min(self.max as f64);
+                }
+            }
+            RequestOutcome::Failed => {
+                state.error_rate += EWMA_ALPHA * (1.0 - state.error_rate);
+            }
+            RequestOutcome::Overloaded => {
+                state.error_rate += EWMA_ALPHA * (1.0 - state.error_rate);
+                let cooled_down = state
+                    .last_decrease
+                    .is_none_or(|at| now.saturating_duration_since(at) >= DECREASE_COOLDOWN);
+                if cooled_down {
+                    state.limit = (state.limit * DECREASE_FACTOR).max(self.min as f64);
+                    state.last_decrease = Some(now);
+                }
+            }
+        }
+
+        let after = state.limit as usize;
+        drop(state);
+
+        if after != before {
+            METRICS.rpc_concurrency_limit.set(after as i64);
+        }
+
+        if after > before {
+            debug!("RPC concurrency limit raised to {}", after);
+            self.released.notify_waiters();
+        } else if after < before {
+            warn!(
+                "RPC endpoints are overloaded, concurrency limit lowered from {} to {}",
+                before, after
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_allows_burst_then_paces() {
+        let bucket = TokenBucket::new(10.0);
+        let start = bucket.state.lock().unwrap().updated;
+
+        for _ in 0..10 {
+            assert_eq!(bucket.reserve(start, 1.0), Duration::ZERO);
+        }
+        assert_eq!(bucket.reserve(start, 1.0).as_millis(), 100);
+        assert_eq!(bucket.reserve(start, 1.0).as_millis(), 200);
+
+        // Half a second later the queue is drained and three tokens have refilled
+        let later = start + Duration::from_millis(500);
+        assert_eq!(bucket.reserve(later, 1.0), Duration::ZERO);
+        assert_eq!(bucket.state.lock().unwrap().tokens.round(), 2.0);
+
+        // A batch reserves the summed cost of its calls, e.g. four calls costing 1 each
+        assert_eq!(bucket.reserve(later, 4.0).as_millis(), 200);
+    }
+
+    #[test]
+    fn test_request_costs() {
+        assert_eq!(request_cost("eth_blockNumber", &Value::Null), 1.0);
+        assert_eq!(
+            request_cost("eth_getBlockByNumber", &serde_json::json!(["0x1", false])),
+            2.0
+        );
+        assert_eq!(
+            request_cost("eth_getBlockByNumber", &serde_json::json!(["0x1", true])),
+            FULL_BLOCK_COST
+        );
+        assert_eq!(
+            request_cost("eth_getBlockReceipts", &serde_json::json!(["0x1"])),
+            50.0
+        );
+        assert_eq!(request_cost("net_version", &Value::Null), 1.0);
+    }
+
+    #[test]
+    fn test_limit_grows_additively_while_healthy() {
+        let controller = AdaptiveConcurrency::new(4, 1, 6);
+        let now = Instant::now();
+
+        // 4 + 1/4 + 1/4.25 + ... crosses 5 on the fifth healthy request
+        for _ in 0..5 {
+            controller.record_at(RequestOutcome::Success(Duration::from_millis(50)), now);
+        }
+        assert_eq!(controller.limit(), 5);
+
+        for _ in 0..100 {
+            controller.record_at(RequestOutcome::Success(Duration::from_millis(50)), now);
+        }
+        assert_eq!(controller.limit(), 6);
+    }
+
+    #[test]
+    fn test_slow_requests_do_not_grow_the_limit() {
+        let controller = AdaptiveConcurrency::new(4, 1, 16);
+        let now = Instant::now();
+
+        controller.record_at(RequestOutcome::Success(Duration::from_millis(50)), now);
+        let limit = controller.state.lock().unwrap().limit;
+        controller.record_at(RequestOutcome::Success(Duration::from_millis(500)), now);
+
+        assert_eq!(controller.state.lock().unwrap().limit, limit);
+    }
+
+    #[test]
+    fn test_overload_halves_the_limit_once_per_episode() {
+        let controller = AdaptiveConcurrency::new(16, 2, 32);
+        let now = Instant::now();
+
+        controller.record_at(RequestOutcome::Overloaded, now);
+        controller.record_at(RequestOutcome::Overloaded, now + Duration::from_millis(100));
+        assert_eq!(controller.limit(), 8);
+
+        controller.record_at(RequestOutcome::Overloaded, now + DECREASE_COOLDOWN);
+        assert_eq!(controller.limit(), 4);
+
+        for step in 2..6 {
+            controller.record_at(RequestOutcome::Overloaded, now + DECREASE_COOLDOWN * step);
+        }
+        assert_eq!(controller.limit(), 2);
+    }
+
+    #[test]
+    fn test_permits_respect_the_limit() {
+        let controller = AdaptiveConcurrency::new(2, 1, 4);
+
+        let first = controller.try_acquire();
+        let second = controller.try_acquire();
+        assert!(first.is_some() && second.is_some());
+        assert!(controller.try_acquire().is_none());
+
+        drop(first);
+        assert!(controller.try_acquire().is_some());
+    }
+}
@@ -0,0 +1,361 @@
+use async_trait::async_trait;
+use ethers::providers::{
+    Http, HttpClientError, HttpRateLimitRetryPolicy, JsonRpcClient, JsonRpcError, ProviderError,
+    RetryPolicy, RpcError,
+};
+use log::warn;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use url::Url;
+
+use crate::metrics::METRICS;
+use crate::throttle::{request_cost, AdaptiveConcurrency, RequestOutcome, TokenBucket};
+
+/// Error of a request sent through `ThrottledHttp`
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error(transparent)]
+    Http(#[from] HttpClientError),
+    #[error("RPC request timed out after {0:?}")]
+    Timeout(Duration),
+    #[error("RPC node responded with HTTP status {0}")]
+    Status(u16),
+}
+
+impl RpcError for TransportError {
+    fn as_error_response(&self) -> Option<&JsonRpcError> {
+        match self {
+            TransportError::Http(e) => e.as_error_response(),
+            _ => None,
+        }
+    }
+
+    fn as_serde_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            TransportError::Http(e) => e.as_serde_error(),
+            _ => None,
+        }
+    }
+}
+
+impl From<TransportError> for ProviderError {
+    fn from(error: TransportError) -> Self {
+        match error {
+            TransportError::Http(e) => e.into(),
+            e => ProviderError::JsonRpcClientError(Box::new(e)),
+        }
+    }
+}
+
+/// One entry of a JSON-RPC batch response
+#[derive(Debug, Deserialize)]
+struct BatchResponse {
+    id: Option<u64>,
+    #[serde(default)]
+    result: Value,
+    error: Option<JsonRpcError>,
+}
+
+/// Match a batch response body to the `count` requests of the batch, whose ids are
+/// `0..count`. Returns `None` when the node did not answer every request, e.g. because it
+/// rejected the batch with a single error object.
+fn parse_batch_response(body: &[u8], count: usize) -> Option<Vec<Result<Value, JsonRpcError>>> {
+    let responses: Vec<BatchResponse> = serde_json::from_slice(body).ok()?;
+
+    let mut results: Vec<Option<Result<Value, JsonRpcError>>> = vec![None; count];
+    for response in responses {
+        let slot = results.get_mut(usize::try_from(response.id?).ok()?)?;
+        *slot = Some(match response.error {
+            Some(error) => Err(error),
+            None => Ok(response.result),
+        });
+    }
+
+    results.into_iter().collect()
+}
+
+/// HTTP transport that paces requests through the endpoint's token bucket, bounds them with a
+/// timeout and reports their outcome to the shared concurrency controller
+#[derive(Debug)]
+pub struct ThrottledHttp {
+    inner: Http,
+    client: reqwest::Client,
+    url: Url,
+    rate_limit: Option<TokenBucket>,
+    timeout: Duration,
+    concurrency: Arc<AdaptiveConcurrency>,
+    /// Largest batch sent in one request; lowered when the node rejects a batch
+    batch_size: AtomicUsize,
+}
+
+impl ThrottledHttp {
+    pub fn new(
+        url: Url,
+        rate_limit: Option<f64>,
+        timeout: Duration,
+        batch_size: usize,
+        concurrency: Arc<AdaptiveConcurrency>,
+    ) -> Self {
+        let client = reqwest::Client::new();
+        ThrottledHttp {
+            inner: Http::new_with_client(url.clone(), client.clone()),
+            client,
+            url,
+            rate_limit: rate_limit.map(TokenBucket::new),
+            timeout,
+            concurrency,
+            batch_size: AtomicUsize::new(batch_size.max(1)),
+        }
+    }
+
+    /// Call `method` once per entry of `params` using JSON-RPC batches. Batches the node
+    /// rejects are split in half and retried, down to single requests. The outer error is a
+    /// transport failure of the whole endpoint; the inner ones belong to individual calls.
+    pub async fn batch_request<R: DeserializeOwned + Send>(
+        &self,
+        method: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<Result<R, TransportError>>, TransportError> {
+        let mut results: Vec<Option<Result<R, TransportError>>> =
+            params.iter().map(|_| None).collect();
+
+        let batch_size = self.batch_size.load(Ordering::Relaxed);
+        let requests: Vec<(usize, Value)> = params.into_iter().enumerate().collect();
+        // Stack of batches still to send, the first batch on top
+        let mut pending: Vec<Vec<(usize, Value)>> = requests
+            .chunks(batch_size)
+            .rev()
+            .map(<[_]>::to_vec)
+            .collect();
+
+        while let Some(mut batch) = pending.pop() {
+            if batch.len() == 1 {
+                let (index, params) = batch.remove(0);
+                match self.request(method, params).await {
+                    Err(e) if e.as_error_response().is_none() && e.as_serde_error().is_none() => {
+                        return Err(e)
+                    }
+                    result => results[index] = Some(result),
+                }
+                continue;
+            }
+
+            match self.send_batch(method, &batch).await? {
+                Some(responses) => {
+                    for ((index, _), response) in batch.iter().zip(responses) {
+                        results[*index] = Some(response);
+                    }
+                }
+                None => {
+                    let half = batch.len() / 2;
+                    let previous = self.batch_size.fetch_min(half, Ordering::Relaxed);
+                    if half < previous {
+                        warn!(
+                            "RPC node {} rejected a batch of {} requests, lowering the batch size to {}",
+                            self.url,
+                            batch.len(),
+                            half
+                        );
+                    }
+                    let second = batch.split_off(half);
+                    pending.push(second);
+                    pending.push(batch);
+                }
+            }
+        }
+
+        Ok(results
+            .into_iter()
+            .map(|result| result.expect("every batched request is answered"))
+            .collect())
+    }
+
+    /// Send one batch, returning `None` if the node rejected it as a whole
+    async fn send_batch<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        batch: &[(usize, Value)],
+    ) -> Result<Option<Vec<Result<R, TransportError>>>, TransportError> {
+        if let Some(bucket) = &self.rate_limit {
+            let cost = batch
+                .iter()
+                .map(|(_, params)| request_cost(method, params))
+                .sum();
+            bucket.acquire(cost).await;
+        }
+
+        let payload: Vec<Value> = batch
+            .iter()
+            .enumerate()
+            .map(|(id, (_, params))| {
+                json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+            })
+            .collect();
+
+        let started = Instant::now();
+        let send = async {
+            let response = self
+                .client
+                .post(self.url.clone())
+                .json(&payload)
+                .send()
+                .await?;
+            let status = response.status();
+            let body = response.bytes().await?;
+            Ok::<_, reqwest::Error>((status, body))
+        };
+
+        let result = tokio::time::timeout(self.timeout, send).await;
+        observe_duration(method, started);
+
+        let (status, body) = match result {
+            Ok(Ok(response)) => response,
+            Ok(Err(e)) => {
+                self.record_failure(method, RequestOutcome::Failed);
+                return Err(HttpClientError::from(e).into());
+            }
+            Err(_) => {
+                self.record_failure(method, RequestOutcome::Overloaded);
+                return Err(TransportError::Timeout(self.timeout));
+            }
+        };
+
+        if status.as_u16() == 429 {
+            self.record_failure(method, RequestOutcome::Overloaded);
+            return Err(TransportError::Status(429));
+        }
+        if status.is_server_error() {
+            self.record_failure(method, RequestOutcome::Failed);
+            return Err(TransportError::Status(status.as_u16()));
+        }
+
+        let Some(responses) = parse_batch_response(&body, batch.len()) else {
+            return Ok(None);
+        };
+
+        let mut overloaded = false;
+        let results = responses
+            .into_iter()
+            .map(|response| {
+                let error = match response {
+                    Ok(result) => match serde_json::from_value(result) {
+                        Ok(value) => return Ok(value),
+                        Err(err) => HttpClientError::SerdeJson {
+                            err,
+                            text: String::from_utf8_lossy(&body).to_string(),
+                        },
+                    },
+                    Err(error) => HttpClientError::JsonRpcError(error),
+                };
+                overloaded |= HttpRateLimitRetryPolicy.should_retry(&error);
+                Err(error.into())
+            })
+            .collect();
+
+        self.concurrency.record(if overloaded {
+            RequestOutcome::Overloaded
+        } else {
+            RequestOutcome::Success(started.elapsed())
+        });
+
+        Ok(Some(results))
+    }
+
+    fn record_failure(&self, method: &str, outcome: RequestOutcome) {
+        self.concurrency.record(outcome);
+        METRICS.rpc_errors.with_label_values(&[method]).inc();
+    }
+}
+
+fn observe_duration(method: &str, started: Instant) {
+    METRICS
+        .rpc_request_duration
+        .with_label_values(&[method])
+        .observe(started.elapsed().as_secs_f64());
+}
+
+#[async_trait]
+impl JsonRpcClient for ThrottledHttp {
+    type Error = TransportError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, TransportError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        if let Some(bucket) = &self.rate_limit {
+            let params = serde_json::to_value(&params).unwrap_or(Value::Null);
+            bucket.acquire(request_cost(method, &params)).await;
+        }
+
+        let started = Instant::now();
+        let result = tokio::time::timeout(self.timeout, self.inner.request(method, params)).await;
+        observe_duration(method, started);
+
+        let (outcome, result) = match result {
+            Ok(Ok(response)) => (RequestOutcome::Success(started.elapsed()), Ok(response)),
+            Ok(Err(e)) if HttpRateLimitRetryPolicy.should_retry(&e) => {
+                (RequestOutcome::Overloaded, Err(e.into()))
+            }
+            // JSON-RPC error responses are answers from a healthy node, e.g. unsupported methods
+            Ok(Err(e)) if e.as_error_response().is_some() => {
+                (RequestOutcome::Success(started.elapsed()), Err(e.into()))
+            }
+            Ok(Err(e)) => (RequestOutcome::Failed, Err(e.into())),
+            Err(_) => (
+                RequestOutcome::Overloaded,
+                Err(TransportError::Timeout(self.timeout)),
+            ),
+        };
+
+        if result.is_err() {
+            METRICS.rpc_errors.with_label_values(&[method]).inc();
+        }
+        self.concurrency.record(outcome);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_batch_response_orders_by_id() {
+        let body = br#"[
+            {"jsonrpc":"2.0","id":1,"result":null},
+            {"jsonrpc":"2.0","id":0,"result":"0x10"},
+            {"jsonrpc":"2.0","id":2,"error":{"code":-32000,"message":"header not found"}}
+        ]"#;
+
+        let results = parse_batch_response(body, 3).unwrap();
+        assert_eq!(results[0].as_ref().unwrap(), &json!("0x10"));
+        assert_eq!(results[1].as_ref().unwrap(), &Value::Null);
+        assert_eq!(results[2].as_ref().unwrap_err().message, "header not found");
+    }
+
+    #[test]
+    fn test_parse_batch_response_detects_rejected_batches() {
+        // Batch limit exceeded, answered with a single error object
+        let rejected =
+            br#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"batch too large"}}"#;
+        assert!(parse_batch_response(rejected, 2).is_none());
+
+        // Truncated batch
+        let truncated = br#"[{"jsonrpc":"2.0","id":0,"result":"0x1"}]"#;
+        assert!(parse_batch_response(truncated, 2).is_none());
+
+        // Unknown ids
+        let unknown =
+            br#"[{"jsonrpc":"2.0","id":0,"result":"0x1"},{"jsonrpc":"2.0","id":5,"result":"0x2"}]"#;
+        assert!(parse_batch_response(unknown, 2).is_none());
+
+        assert!(parse_batch_response(b"Request Entity Too Large", 2).is_none());
+    }
+}
@@ -0,0 +1,204 @@
+use crate::models::IndexedBlock;
+
+/// Options of a consistency verification run
+#[derive(Debug, Clone, Default)]
+pub struct VerifyOptions {
+    /// First block to check (default: `START_BLOCK`)
+    pub from: Option<u64>,
+    /// Last block to check (default: the checkpoint)
+    pub to: Option<u64>,
+    /// Check only this many randomly chosen blocks instead of the whole range
+    pub sample: Option<u64>,
+    /// Re-index blocks that do not match the chain
+    pub fix: bool,
+}
+
+/// Fields of a canonical block that are compared against the stored copy
+#[derive(Debug, Clone)]
+pub struct ChainBlock {
+    pub hash: String,
+    pub parent_hash: String,
+    pub transaction_hashes: Vec<String>,
+}
+
+/// Differences between a stored block and the chain; empty when they agree
+pub fn compare_block(stored: Option<&IndexedBlock>, chain: &ChainBlock) -> Vec<String> {
+    let Some(stored) = stored else {
+        return vec!["missing from index".to_string()];
+    };
+
+    let mut mismatches = Vec::new();
+
+    if stored.hash != chain.hash {
+        mismatches.push(format!("hash {} != {}", stored.hash, chain.hash));
+    }
+    if stored.parent_hash != chain.parent_hash {
+        mismatches.push(format!(
+            "parent_hash {} != {}",
+            stored.parent_hash, chain.parent_hash
+        ));
+    }
+    if stored.transaction_count != chain.transaction_hashes.len() {
+        mismatches.push(format!(
+            "transaction_count {} != {}",
+            stored.transaction_count,
+            chain.transaction_hashes.len()
+        ));
+    }
+
+    // Sinks load the transactions of flat-only storage from where they are stored, so missing
+    // transaction documents show up here too
+    let stored_hashes = stored.transactions.iter().map(|tx| &tx.hash);
+    let differing = stored_hashes
+        .zip(&chain.transaction_hashes)
+        .filter(|(stored, chain)| stored != chain)
+        .count();
+    if differing > 0 || stored.transactions.len() != chain.transaction_hashes.len() {
+        mismatches.push(format!(
+            "{} transaction hash(es) differ",
+            differing
+                + stored
+                    .transactions
+                    .len()
+                    .abs_diff(chain.transaction_hashes.len())
+        ));
+    }
+
+    mismatches
+}
+
+/// Pick `count` distinct blocks from `from..=to`, or the whole range if it is not larger
+pub fn sample_blocks(from: u64, to: u64, count: u64, seed: u64) -> Vec<u64> {
+    let span = to - from + 1;
+    if count >= span {
+        return (from..=to).collect();
+    }
+
+    // xorshift64; the sample only needs to be spread out, not unpredictable
+    let mut state = seed.max(1);
+    let mut picked = std::collections::BTreeSet::new();
+    while (picked.len() as u64) < count {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        picked.insert(from + state % span);
+    }
+
+    picked.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FinalityStatus, IndexedTransaction};
+
+    fn stored_block(hash: &str, tx_hashes: &[&str]) -> IndexedBlock {
+        IndexedBlock {
+            number: 10,
+            hash: hash.to_string(),
+            parent_hash: "0xparent".to_string(),
+            timestamp: 0,
+            gas_limit: 0,
+            gas_used: 0,
+            miner: None,
+            difficulty: "0".to_string(),
+            total_difficulty: "0".to_string(),
+            size: 0,
+            transactions: tx_hashes
+                .iter()
+                .map(|hash| IndexedTransaction {
+                    hash: hash.to_string(),
+                    from: "0xfrom".to_string(),
+                    to: None,
+                    value: "0".to_string(),
+                    gas: 0,
+                    gas_price: "0".to_string(),
+                    input: "".to_string(),
+                    nonce: 0,
+                    transaction_index: None,
+                    status: None,
+                    gas_used: None,
+                    cumulative_gas_used: None,
+                    effective_gas_price: None,
+                    contract_address: None,
+                    logs_bloom: None,
+                    transaction_type: None,
+                    max_fee_per_gas: None,
+                    max_priority_fee_per_gas: None,
+                    max_fee_per_blob_gas: None,
+                    access_list: None,
+                    chain_id: None,
+                    blob_versioned_hashes: None,
+                })
+                .collect(),
+            transaction_count: tx_hashes.len(),
+            uncles: 0,
+            indexed_at: 0,
+            finality: FinalityStatus::Unfinalized,
+            base_fee_per_gas: None,
+            withdrawals_root: None,
+            blob_gas_used: None,
+            excess_blob_gas: None,
+            withdrawals: vec![],
+            logs: vec![],
+        }
+    }
+
+    fn chain_block(hash: &str, tx_hashes: &[&str]) -> ChainBlock {
+        ChainBlock {
+            hash: hash.to_string(),
+            parent_hash: "0xparent".to_string(),
+            transaction_hashes: tx_hashes.iter().map(|h| h.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_matching_block() {
+        let stored = stored_block("0xabc", &["0x1", "0x2"]);
+        let chain = chain_block("0xabc", &["0x1", "0x2"]);
+
+        assert!(compare_block(Some(&stored), &chain).is_empty());
+    }
+
+    #[test]
+    fn test_missing_block() {
+        let chain = chain_block("0xabc", &[]);
+
+        assert_eq!(compare_block(None, &chain), vec!["missing from index"]);
+    }
+
+    #[test]
+    fn test_mismatched_hash_and_transactions() {
+        let stored = stored_block("0xorphan", &["0x1", "0x3"]);
+        let chain = chain_block("0xabc", &["0x1", "0x2", "0x4"]);
+
+        let mismatches = compare_block(Some(&stored), &chain);
+        assert_eq!(mismatches.len(), 3);
+        assert!(mismatches[0].starts_with("hash"));
+        assert!(mismatches[1].starts_with("transaction_count"));
+        assert_eq!(mismatches[2], "2 transaction hash(es) differ");
+    }
+
+    #[test]
+    fn test_missing_flat_transactions() {
+        // A flat-only block whose transaction documents were never written
+        let mut stored = stored_block("0xabc", &["0x1"]);
+        stored.transactions.clear();
+        let chain = chain_block("0xabc", &["0x1"]);
+
+        assert_eq!(
+            compare_block(Some(&stored), &chain),
+            vec!["1 transaction hash(es) differ"]
+        );
+    }
+
+    #[test]
+    fn test_sample_blocks() {
+        let sample = sample_blocks(100, 199, 10, 42);
+        assert_eq!(sample.len(), 10);
+        assert!(sample.iter().all(|b| (100..=199).contains(b)));
+        assert!(sample.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(sample_blocks(5, 7, 10, 42), vec![5, 6, 7]);
+    }
+}